@@ -0,0 +1,764 @@
+use std::collections::HashSet;
+
+use crate::{castling::Castling, chess_board::ChessBoard, chess_color::ChessColor, chess_move::ChessMove, chess_square::ChessSquare, zobrist::{ZOBRIST_KEYS, get_piece_index}};
+
+// Squares are numbered y * 8 + x, so a1 is 0, h1 is 7 and h8 is 63, the same layout as ChessBoard.squares.
+// Pieces are numbered as in zobrist::get_piece_index: color * 6 + kind.
+pub const PAWN: u8 = 0;
+pub const ROOK: u8 = 1;
+pub const KNIGHT: u8 = 2;
+pub const BISHOP: u8 = 3;
+pub const QUEEN: u8 = 4;
+pub const KING: u8 = 5;
+pub const NO_PIECE: u8 = 12;
+
+pub const WHITE: usize = 0;
+pub const BLACK: usize = 1;
+
+pub const WHITE_SHORT: u8 = 1;
+pub const WHITE_LONG: u8 = 2;
+pub const BLACK_SHORT: u8 = 4;
+pub const BLACK_LONG: u8 = 8;
+
+pub const FLAG_CASTLING: u8 = 1;
+pub const FLAG_EN_PASSANT: u8 = 2;
+pub const FLAG_DOUBLE_PUSH: u8 = 4;
+
+pub const FILE_A: u64 = 0x0101010101010101;
+const RANK_1: u64 = 0xFF;
+const RANK_8: u64 = 0xFF << 56;
+// b1, d1, ... the squares where (x + y) is odd
+const LIGHT_SQUARES: u64 = 0x55AA55AA55AA55AA;
+
+// enough for any legal chess position, the known maximum is 218
+pub const MAX_MOVES: usize = 256;
+
+#[derive(Eq, PartialEq, Debug, Clone, Copy)]
+pub struct BitMove {
+  pub from: u8,
+  pub to: u8,
+  pub piece: u8,
+  pub capture: u8,
+  pub promotion: u8,
+  pub flags: u8,
+}
+
+const NULL_MOVE: BitMove = BitMove{ from: 0, to: 0, piece: NO_PIECE, capture: NO_PIECE, promotion: NO_PIECE, flags: 0 };
+
+// a fixed-capacity move list that lives on the stack
+pub struct MoveList {
+  moves: [BitMove; MAX_MOVES],
+  len: usize,
+}
+
+impl Default for MoveList {
+  fn default() -> MoveList {
+    return MoveList::new();
+  }
+}
+
+impl MoveList {
+  pub fn new() -> MoveList {
+    return MoveList{ moves: [NULL_MOVE; MAX_MOVES], len: 0 };
+  }
+
+  pub fn push(&mut self, m: BitMove) {
+    self.moves[self.len] = m;
+    self.len += 1;
+  }
+
+  pub fn clear(&mut self) {
+    self.len = 0;
+  }
+
+  pub fn len(&self) -> usize {
+    return self.len;
+  }
+
+  pub fn is_empty(&self) -> bool {
+    return self.len == 0;
+  }
+
+  pub fn as_slice(&self) -> &[BitMove] {
+    return &self.moves[..self.len];
+  }
+
+  pub fn as_mut_slice(&mut self) -> &mut [BitMove] {
+    return &mut self.moves[..self.len];
+  }
+}
+
+#[derive(Eq, PartialEq, Debug, Clone, Copy)]
+pub struct Position {
+  pub pieces: [u64; 12],
+  // all white pieces, then all black pieces
+  pub colors: [u64; 2],
+  pub side_to_move: usize,
+  pub castling: u8,
+  pub en_passant: Option<u8>,
+  pub halfmove_clock: u32,
+  pub fullmove_number: u32,
+  // kept up to date by make_move, always equal to compute_hash
+  pub hash: u64,
+  // the same for the pawns alone, for caching pawn structure
+  pub pawn_hash: u64,
+}
+
+// what make_move changes that unmake_move can't work out from the move
+#[derive(Debug, Clone, Copy)]
+pub struct PositionUndo {
+  castling: u8,
+  en_passant: Option<u8>,
+  halfmove_clock: u32,
+  hash: u64,
+  pawn_hash: u64,
+}
+
+const fn square_bit(x: i32, y: i32) -> u64 {
+  if x < 0 || x > 7 || y < 0 || y > 7 {
+    return 0;
+  }
+  return 1 << (y * 8 + x);
+}
+
+const fn generate_step_attacks(steps: [(i32, i32); 8]) -> [u64; 64] {
+  let mut table = [0; 64];
+  let mut square = 0;
+  while square < 64 {
+    let x = square as i32 % 8;
+    let y = square as i32 / 8;
+    let mut i = 0;
+    while i < 8 {
+      table[square] |= square_bit(x + steps[i].0, y + steps[i].1);
+      i += 1;
+    }
+    square += 1;
+  }
+  return table;
+}
+
+const fn generate_pawn_attacks() -> [[u64; 64]; 2] {
+  let mut table = [[0; 64]; 2];
+  let mut square = 0;
+  while square < 64 {
+    let x = square as i32 % 8;
+    let y = square as i32 / 8;
+    table[WHITE][square] = square_bit(x - 1, y + 1) | square_bit(x + 1, y + 1);
+    table[BLACK][square] = square_bit(x - 1, y - 1) | square_bit(x + 1, y - 1);
+    square += 1;
+  }
+  return table;
+}
+
+// the first four directions run towards higher square numbers, the last four towards lower ones
+const DIRECTIONS: [(i32, i32); 8] = [(0, 1), (1, 1), (1, 0), (-1, 1), (0, -1), (-1, -1), (-1, 0), (1, -1)];
+const NORTH: usize = 0;
+const NORTH_EAST: usize = 1;
+const EAST: usize = 2;
+const NORTH_WEST: usize = 3;
+const SOUTH: usize = 4;
+const SOUTH_WEST: usize = 5;
+const WEST: usize = 6;
+const SOUTH_EAST: usize = 7;
+
+const fn generate_rays() -> [[u64; 64]; 8] {
+  let mut table = [[0; 64]; 8];
+  let mut direction = 0;
+  while direction < 8 {
+    let mut square = 0;
+    while square < 64 {
+      let mut x = square as i32 % 8 + DIRECTIONS[direction].0;
+      let mut y = square as i32 / 8 + DIRECTIONS[direction].1;
+      while x >= 0 && x <= 7 && y >= 0 && y <= 7 {
+        table[direction][square] |= square_bit(x, y);
+        x += DIRECTIONS[direction].0;
+        y += DIRECTIONS[direction].1;
+      }
+      square += 1;
+    }
+    direction += 1;
+  }
+  return table;
+}
+
+pub const KNIGHT_ATTACKS: [u64; 64] = generate_step_attacks([(-1, 2), (1, 2), (2, 1), (2, -1), (-1, -2), (1, -2), (-2, 1), (-2, -1)]);
+pub const KING_ATTACKS: [u64; 64] = generate_step_attacks([(-1, 0), (1, 0), (0, -1), (0, 1), (-1, -1), (-1, 1), (1, 1), (1, -1)]);
+// squares a pawn of the given color standing on the square attacks
+pub const PAWN_ATTACKS: [[u64; 64]; 2] = generate_pawn_attacks();
+const RAYS: [[u64; 64]; 8] = generate_rays();
+
+fn get_ray_attacks(square: usize, occupied: u64, direction: usize) -> u64 {
+  let ray = RAYS[direction][square];
+  let blockers = ray & occupied;
+  if blockers == 0 {
+    return ray;
+  }
+  let first_blocker = if direction < 4 { blockers.trailing_zeros() } else { 63 - blockers.leading_zeros() };
+  return ray ^ RAYS[direction][first_blocker as usize];
+}
+
+pub fn get_rook_attacks(square: usize, occupied: u64) -> u64 {
+  return get_ray_attacks(square, occupied, NORTH)
+    | get_ray_attacks(square, occupied, EAST)
+    | get_ray_attacks(square, occupied, SOUTH)
+    | get_ray_attacks(square, occupied, WEST);
+}
+
+pub fn get_bishop_attacks(square: usize, occupied: u64) -> u64 {
+  return get_ray_attacks(square, occupied, NORTH_EAST)
+    | get_ray_attacks(square, occupied, NORTH_WEST)
+    | get_ray_attacks(square, occupied, SOUTH_EAST)
+    | get_ray_attacks(square, occupied, SOUTH_WEST);
+}
+
+// every square attacked by any of the pawns of the given color
+pub fn get_pawn_attacks(pawns: u64, color: usize) -> u64 {
+  let not_a = !FILE_A;
+  let not_h = !(FILE_A << 7);
+  return if color == WHITE {
+    ((pawns << 7) & not_h) | ((pawns << 9) & not_a)
+  } else {
+    ((pawns >> 9) & not_h) | ((pawns >> 7) & not_a)
+  };
+}
+
+pub fn get_piece_for_index(piece: u8) -> ChessSquare {
+  return match piece {
+    0 => ChessSquare::WhitePawn,
+    1 => ChessSquare::WhiteRook,
+    2 => ChessSquare::WhiteKnight,
+    3 => ChessSquare::WhiteBishop,
+    4 => ChessSquare::WhiteQueen,
+    5 => ChessSquare::WhiteKing,
+    6 => ChessSquare::BlackPawn,
+    7 => ChessSquare::BlackRook,
+    8 => ChessSquare::BlackKnight,
+    9 => ChessSquare::BlackBishop,
+    10 => ChessSquare::BlackQueen,
+    11 => ChessSquare::BlackKing,
+    _ => ChessSquare::Empty,
+  };
+}
+
+pub fn get_castling_bit(castling: &Castling) -> u8 {
+  return match castling {
+    Castling::WhiteShort => WHITE_SHORT,
+    Castling::WhiteLong => WHITE_LONG,
+    Castling::BlackShort => BLACK_SHORT,
+    Castling::BlackLong => BLACK_LONG,
+  };
+}
+
+// castling rights lost when a move starts or ends on the square, because a king or rook left or was captured there
+const fn generate_castling_masks() -> [u8; 64] {
+  let mut masks = [0; 64];
+  masks[0] = WHITE_LONG;
+  masks[4] = WHITE_SHORT | WHITE_LONG;
+  masks[7] = WHITE_SHORT;
+  masks[56] = BLACK_LONG;
+  masks[60] = BLACK_SHORT | BLACK_LONG;
+  masks[63] = BLACK_SHORT;
+  return masks;
+}
+
+const CASTLING_MASKS: [u8; 64] = generate_castling_masks();
+
+fn get_castling_bits_key(castling: u8) -> u64 {
+  let mut key = 0;
+  for i in 0..4 {
+    if castling & (1 << i) != 0 {
+      key ^= ZOBRIST_KEYS.castling[i];
+    }
+  }
+  return key;
+}
+
+impl BitMove {
+  pub fn is_capture(&self) -> bool {
+    return self.capture != NO_PIECE;
+  }
+
+  pub fn to_chess_move(&self) -> ChessMove {
+    let castling = if self.flags & FLAG_CASTLING == 0 {
+      None
+    } else {
+      Some(match self.to {
+        6 => Castling::WhiteShort,
+        2 => Castling::WhiteLong,
+        62 => Castling::BlackShort,
+        _ => Castling::BlackLong,
+      })
+    };
+    return ChessMove{
+      piece: get_piece_for_index(self.piece),
+      x: self.from as usize % 8,
+      y: self.from as usize / 8,
+      to_x: self.to as usize % 8,
+      to_y: self.to as usize / 8,
+      capture: if self.capture == NO_PIECE { None } else { Some(get_piece_for_index(self.capture)) },
+      promotion: if self.promotion == NO_PIECE { None } else { Some(get_piece_for_index(self.promotion)) },
+      castling: castling,
+      en_pessant: self.flags & FLAG_EN_PASSANT != 0,
+    };
+  }
+}
+
+pub fn from_chess_move(chess_move: &ChessMove) -> BitMove {
+  let is_pawn = chess_move.piece == ChessSquare::WhitePawn || chess_move.piece == ChessSquare::BlackPawn;
+  let mut flags = 0;
+  if chess_move.castling.is_some() {
+    flags |= FLAG_CASTLING;
+  }
+  if chess_move.en_pessant {
+    flags |= FLAG_EN_PASSANT;
+  }
+  if is_pawn && (chess_move.to_y as i32 - chess_move.y as i32).abs() == 2 {
+    flags |= FLAG_DOUBLE_PUSH;
+  }
+  return BitMove{
+    from: (chess_move.y * 8 + chess_move.x) as u8,
+    to: (chess_move.to_y * 8 + chess_move.to_x) as u8,
+    piece: get_piece_index(&chess_move.piece) as u8,
+    capture: chess_move.capture.as_ref().map_or(NO_PIECE, |c| get_piece_index(c) as u8),
+    promotion: chess_move.promotion.as_ref().map_or(NO_PIECE, |p| get_piece_index(p) as u8),
+    flags: flags,
+  };
+}
+
+impl Position {
+  pub fn get_occupied(&self) -> u64 {
+    return self.colors[WHITE] | self.colors[BLACK];
+  }
+
+  pub fn get_piece_at(&self, square: usize) -> u8 {
+    let bit = 1 << square;
+    if self.get_occupied() & bit == 0 {
+      return NO_PIECE;
+    }
+    for piece in 0..12 {
+      if self.pieces[piece] & bit != 0 {
+        return piece as u8;
+      }
+    }
+    return NO_PIECE;
+  }
+
+  pub fn get_king_square(&self, color: usize) -> usize {
+    return self.pieces[color * 6 + KING as usize].trailing_zeros() as usize;
+  }
+
+  // whether any piece of the given color attacks the square
+  pub fn is_square_attacked(&self, square: usize, by_color: usize) -> bool {
+    let base = by_color * 6;
+    let occupied = self.get_occupied();
+    if PAWN_ATTACKS[1 - by_color][square] & self.pieces[base + PAWN as usize] != 0 {
+      return true;
+    }
+    if KNIGHT_ATTACKS[square] & self.pieces[base + KNIGHT as usize] != 0 {
+      return true;
+    }
+    if KING_ATTACKS[square] & self.pieces[base + KING as usize] != 0 {
+      return true;
+    }
+    let queens = self.pieces[base + QUEEN as usize];
+    if get_bishop_attacks(square, occupied) & (self.pieces[base + BISHOP as usize] | queens) != 0 {
+      return true;
+    }
+    return get_rook_attacks(square, occupied) & (self.pieces[base + ROOK as usize] | queens) != 0;
+  }
+
+  pub fn is_in_check(&self) -> bool {
+    return self.is_square_attacked(self.get_king_square(self.side_to_move), 1 - self.side_to_move);
+  }
+
+  fn move_piece(&mut self, piece: u8, from: u8, to: u8) {
+    let bits = (1u64 << from) | (1u64 << to);
+    self.pieces[piece as usize] ^= bits;
+    self.colors[piece as usize / 6] ^= bits;
+  }
+
+  fn toggle_piece(&mut self, piece: u8, square: u8) {
+    self.pieces[piece as usize] ^= 1u64 << square;
+    self.colors[piece as usize / 6] ^= 1u64 << square;
+  }
+
+  // the piece placement part of a move, which is its own inverse
+  fn toggle_move_pieces(&mut self, m: &BitMove, side: usize) {
+    if m.flags & FLAG_EN_PASSANT != 0 {
+      let captured_square = if side == WHITE { m.to - 8 } else { m.to + 8 };
+      self.toggle_piece(m.capture, captured_square);
+    } else if m.capture != NO_PIECE {
+      self.toggle_piece(m.capture, m.to);
+    }
+    if m.promotion != NO_PIECE {
+      self.toggle_piece(m.piece, m.from);
+      self.toggle_piece(m.promotion, m.to);
+    } else {
+      self.move_piece(m.piece, m.from, m.to);
+    }
+    if m.flags & FLAG_CASTLING != 0 {
+      let rook = (side * 6) as u8 + ROOK;
+      match m.to {
+        6 => self.move_piece(rook, 7, 5),
+        2 => self.move_piece(rook, 0, 3),
+        62 => self.move_piece(rook, 63, 61),
+        _ => self.move_piece(rook, 56, 59),
+      }
+    }
+  }
+
+  // xor of the piece-square keys a move changes
+  fn get_move_hash_delta(&self, m: &BitMove) -> u64 {
+    let keys = &ZOBRIST_KEYS.pieces;
+    let mut delta = keys[m.piece as usize][m.from as usize];
+    delta ^= keys[if m.promotion != NO_PIECE { m.promotion } else { m.piece } as usize][m.to as usize];
+    if m.flags & FLAG_EN_PASSANT != 0 {
+      let captured_square = if self.side_to_move == WHITE { m.to - 8 } else { m.to + 8 };
+      delta ^= keys[m.capture as usize][captured_square as usize];
+    } else if m.capture != NO_PIECE {
+      delta ^= keys[m.capture as usize][m.to as usize];
+    }
+    if m.flags & FLAG_CASTLING != 0 {
+      let rook = self.side_to_move * 6 + ROOK as usize;
+      let (from, to) = match m.to {
+        6 => (7, 5),
+        2 => (0, 3),
+        62 => (63, 61),
+        _ => (56, 59),
+      };
+      delta ^= keys[rook][from] ^ keys[rook][to];
+    }
+    return delta;
+  }
+
+  // xor of the piece-square keys of the pawns a move moves, removes or promotes; the side to move has already
+  // changed when make_move calls this
+  fn get_pawn_hash_delta(&self, m: &BitMove) -> u64 {
+    let keys = &ZOBRIST_KEYS.pieces;
+    let mut delta = 0;
+    if m.piece % 6 == PAWN {
+      delta ^= keys[m.piece as usize][m.from as usize];
+      if m.promotion == NO_PIECE {
+        delta ^= keys[m.piece as usize][m.to as usize];
+      }
+    }
+    if m.flags & FLAG_EN_PASSANT != 0 {
+      let captured_square = if m.piece == PAWN { m.to - 8 } else { m.to + 8 };
+      delta ^= keys[m.capture as usize][captured_square as usize];
+    } else if m.capture != NO_PIECE && m.capture % 6 == PAWN {
+      delta ^= keys[m.capture as usize][m.to as usize];
+    }
+    return delta;
+  }
+
+  // the en passant file only counts towards the hash while a pawn of the side to move can capture there
+  fn get_en_passant_key(&self) -> u64 {
+    if let Some(ep) = self.en_passant {
+      let capturers = PAWN_ATTACKS[1 - self.side_to_move][ep as usize] & self.pieces[self.side_to_move * 6 + PAWN as usize];
+      if capturers != 0 {
+        return ZOBRIST_KEYS.en_passant_file[ep as usize % 8];
+      }
+    }
+    return 0;
+  }
+
+  // plays a move produced by this position's generator, legal or not, and returns what unmake_move needs
+  pub fn make_move(&mut self, m: &BitMove) -> PositionUndo {
+    let undo = PositionUndo{
+      castling: self.castling,
+      en_passant: self.en_passant,
+      halfmove_clock: self.halfmove_clock,
+      hash: self.hash,
+      pawn_hash: self.pawn_hash,
+    };
+    let mut hash = self.hash ^ self.get_move_hash_delta(m) ^ self.get_en_passant_key() ^ get_castling_bits_key(self.castling);
+    self.toggle_move_pieces(m, self.side_to_move);
+    self.castling &= !(CASTLING_MASKS[m.from as usize] | CASTLING_MASKS[m.to as usize]);
+    self.en_passant = if m.flags & FLAG_DOUBLE_PUSH != 0 { Some((m.from + m.to) / 2) } else { None };
+    self.halfmove_clock = if m.piece % 6 == PAWN || m.capture != NO_PIECE { 0 } else { self.halfmove_clock + 1 };
+    if self.side_to_move == BLACK {
+      self.fullmove_number += 1;
+    }
+    self.side_to_move = 1 - self.side_to_move;
+    hash ^= ZOBRIST_KEYS.black_to_move ^ get_castling_bits_key(self.castling) ^ self.get_en_passant_key();
+    self.hash = hash;
+    self.pawn_hash ^= self.get_pawn_hash_delta(m);
+    debug_assert_eq!(self.hash, self.compute_hash(), "incremental hash diverged after {}", m.to_chess_move().to_uci());
+    debug_assert_eq!(self.pawn_hash, self.compute_pawn_hash(), "incremental pawn hash diverged after {}", m.to_chess_move().to_uci());
+    return undo;
+  }
+
+  // takes back the last move made, given the record make_move returned for it
+  pub fn unmake_move(&mut self, m: &BitMove, undo: PositionUndo) {
+    self.side_to_move = 1 - self.side_to_move;
+    if self.side_to_move == BLACK {
+      self.fullmove_number -= 1;
+    }
+    self.toggle_move_pieces(m, self.side_to_move);
+    self.castling = undo.castling;
+    self.en_passant = undo.en_passant;
+    self.halfmove_clock = undo.halfmove_clock;
+    self.hash = undo.hash;
+    self.pawn_hash = undo.pawn_hash;
+  }
+
+  // passes the turn without moving, for null-move pruning; never legal in a game
+  pub fn make_null_move(&mut self) -> PositionUndo {
+    let undo = PositionUndo{
+      castling: self.castling,
+      en_passant: self.en_passant,
+      halfmove_clock: self.halfmove_clock,
+      hash: self.hash,
+      pawn_hash: self.pawn_hash,
+    };
+    self.hash ^= self.get_en_passant_key() ^ ZOBRIST_KEYS.black_to_move;
+    self.en_passant = None;
+    self.halfmove_clock += 1;
+    if self.side_to_move == BLACK {
+      self.fullmove_number += 1;
+    }
+    self.side_to_move = 1 - self.side_to_move;
+    debug_assert_eq!(self.hash, self.compute_hash(), "incremental hash diverged after a null move");
+    return undo;
+  }
+
+  pub fn unmake_null_move(&mut self, undo: PositionUndo) {
+    self.side_to_move = 1 - self.side_to_move;
+    if self.side_to_move == BLACK {
+      self.fullmove_number -= 1;
+    }
+    self.en_passant = undo.en_passant;
+    self.halfmove_clock = undo.halfmove_clock;
+    self.hash = undo.hash;
+  }
+
+  // whether the color has anything besides its king and pawns, which is when zugzwang becomes unlikely
+  pub fn has_non_pawn_material(&self, color: usize) -> bool {
+    let pawns_and_king = self.pieces[color * 6 + PAWN as usize] | self.pieces[color * 6 + KING as usize];
+    return self.colors[color] & !pawns_and_king != 0;
+  }
+
+  // the same rules as ChessBoard::has_insufficient_material: a lone minor piece, or only bishops all on one color
+  pub fn has_insufficient_material(&self) -> bool {
+    let kings = self.pieces[KING as usize] | self.pieces[6 + KING as usize];
+    let knights = self.pieces[KNIGHT as usize] | self.pieces[6 + KNIGHT as usize];
+    let bishops = self.pieces[BISHOP as usize] | self.pieces[6 + BISHOP as usize];
+    if self.get_occupied() != kings | knights | bishops {
+      return false;
+    }
+    if (knights | bishops).count_ones() <= 1 {
+      return true;
+    }
+    return knights == 0 && (bishops & LIGHT_SQUARES == 0 || bishops & !LIGHT_SQUARES == 0);
+  }
+
+  fn push_pawn_moves(&self, from: u8, to: u8, capture: u8, flags: u8, moves: &mut MoveList) {
+    let piece = (self.side_to_move * 6) as u8 + PAWN;
+    if (1u64 << to) & (RANK_1 | RANK_8) != 0 {
+      let base = (self.side_to_move * 6) as u8;
+      for promotion in [BISHOP, KNIGHT, QUEEN, ROOK] {
+        moves.push(BitMove{ from: from, to: to, piece: piece, capture: capture, promotion: base + promotion, flags: flags });
+      }
+    } else {
+      moves.push(BitMove{ from: from, to: to, piece: piece, capture: capture, promotion: NO_PIECE, flags: flags });
+    }
+  }
+
+  fn push_targets(&self, piece: u8, from: usize, targets: u64, moves: &mut MoveList) {
+    let enemies = self.colors[1 - self.side_to_move];
+    let mut remaining = targets;
+    while remaining != 0 {
+      let to = remaining.trailing_zeros() as u8;
+      remaining &= remaining - 1;
+      let capture = if enemies & (1u64 << to) != 0 { self.get_piece_at(to as usize) } else { NO_PIECE };
+      moves.push(BitMove{ from: from as u8, to: to, piece: piece, capture: capture, promotion: NO_PIECE, flags: 0 });
+    }
+  }
+
+  // every move that follows the piece movement rules, including ones that leave the own king in check
+  pub fn generate_pseudo_legal_moves(&self, moves: &mut MoveList) {
+    let us = self.side_to_move;
+    let them = 1 - us;
+    let base = (us * 6) as u8;
+    let own = self.colors[us];
+    let enemies = self.colors[them];
+    let occupied = own | enemies;
+
+    let mut pawns = self.pieces[(base + PAWN) as usize];
+    while pawns != 0 {
+      let from = pawns.trailing_zeros() as u8;
+      pawns &= pawns - 1;
+      let (single, start_rank) = if us == WHITE { (from + 8, 1) } else { (from - 8, 6) };
+      if occupied & (1u64 << single) == 0 {
+        self.push_pawn_moves(from, single, NO_PIECE, 0, moves);
+        let double = if us == WHITE { from + 16 } else { from.wrapping_sub(16) };
+        if from / 8 == start_rank && occupied & (1u64 << double) == 0 {
+          self.push_pawn_moves(from, double, NO_PIECE, FLAG_DOUBLE_PUSH, moves);
+        }
+      }
+      let mut captures = PAWN_ATTACKS[us][from as usize] & enemies;
+      while captures != 0 {
+        let to = captures.trailing_zeros() as u8;
+        captures &= captures - 1;
+        self.push_pawn_moves(from, to, self.get_piece_at(to as usize), 0, moves);
+      }
+      if let Some(ep) = self.en_passant {
+        if PAWN_ATTACKS[us][from as usize] & (1u64 << ep) != 0 {
+          let enemy_pawn = (them * 6) as u8 + PAWN;
+          self.push_pawn_moves(from, ep, enemy_pawn, FLAG_EN_PASSANT, moves);
+        }
+      }
+    }
+
+    for kind in [KNIGHT, BISHOP, ROOK, QUEEN, KING] {
+      let piece = base + kind;
+      let mut bits = self.pieces[piece as usize];
+      while bits != 0 {
+        let from = bits.trailing_zeros() as usize;
+        bits &= bits - 1;
+        let attacks = match kind {
+          KNIGHT => KNIGHT_ATTACKS[from],
+          BISHOP => get_bishop_attacks(from, occupied),
+          ROOK => get_rook_attacks(from, occupied),
+          QUEEN => get_bishop_attacks(from, occupied) | get_rook_attacks(from, occupied),
+          _ => KING_ATTACKS[from],
+        };
+        self.push_targets(piece, from, attacks & !own, moves);
+      }
+    }
+
+    // castling only needs the path to be empty here, the attacked squares are checked with legality
+    let king = base + KING;
+    let rook = base + ROOK;
+    let (short, long, home) = if us == WHITE { (WHITE_SHORT, WHITE_LONG, 0u8) } else { (BLACK_SHORT, BLACK_LONG, 56u8) };
+    let has_king = self.pieces[king as usize] & (1u64 << (home + 4)) != 0;
+    if has_king && self.castling & short != 0 && self.pieces[rook as usize] & (1u64 << (home + 7)) != 0 && occupied & (0b0110_0000u64 << home) == 0 {
+      moves.push(BitMove{ from: home + 4, to: home + 6, piece: king, capture: NO_PIECE, promotion: NO_PIECE, flags: FLAG_CASTLING });
+    }
+    if has_king && self.castling & long != 0 && self.pieces[rook as usize] & (1u64 << home) != 0 && occupied & (0b0000_1110u64 << home) == 0 {
+      moves.push(BitMove{ from: home + 4, to: home + 2, piece: king, capture: NO_PIECE, promotion: NO_PIECE, flags: FLAG_CASTLING });
+    }
+  }
+
+  pub fn is_legal(&self, m: &BitMove) -> bool {
+    let us = self.side_to_move;
+    let them = 1 - us;
+    if m.flags & FLAG_CASTLING != 0 {
+      let step: i32 = if m.to > m.from { 1 } else { -1 };
+      for i in 0..3 {
+        if self.is_square_attacked((m.from as i32 + step * i) as usize, them) {
+          return false;
+        }
+      }
+      return true;
+    }
+    // only the pieces matter for whether the king is left attacked, so the rest of make_move is skipped
+    let mut next = *self;
+    next.toggle_move_pieces(m, us);
+    return !next.is_square_attacked(next.get_king_square(us), them);
+  }
+
+  pub fn generate_legal_moves(&self, moves: &mut MoveList) {
+    let mut pseudo_legal = MoveList::new();
+    self.generate_pseudo_legal_moves(&mut pseudo_legal);
+    moves.clear();
+    for m in pseudo_legal.as_slice() {
+      if self.is_legal(m) {
+        moves.push(*m);
+      }
+    }
+  }
+
+  pub fn to_chess_board(&self) -> ChessBoard {
+    let mut squares = [
+      [ChessSquare::Empty, ChessSquare::Empty, ChessSquare::Empty, ChessSquare::Empty, ChessSquare::Empty, ChessSquare::Empty, ChessSquare::Empty, ChessSquare::Empty],
+      [ChessSquare::Empty, ChessSquare::Empty, ChessSquare::Empty, ChessSquare::Empty, ChessSquare::Empty, ChessSquare::Empty, ChessSquare::Empty, ChessSquare::Empty],
+      [ChessSquare::Empty, ChessSquare::Empty, ChessSquare::Empty, ChessSquare::Empty, ChessSquare::Empty, ChessSquare::Empty, ChessSquare::Empty, ChessSquare::Empty],
+      [ChessSquare::Empty, ChessSquare::Empty, ChessSquare::Empty, ChessSquare::Empty, ChessSquare::Empty, ChessSquare::Empty, ChessSquare::Empty, ChessSquare::Empty],
+      [ChessSquare::Empty, ChessSquare::Empty, ChessSquare::Empty, ChessSquare::Empty, ChessSquare::Empty, ChessSquare::Empty, ChessSquare::Empty, ChessSquare::Empty],
+      [ChessSquare::Empty, ChessSquare::Empty, ChessSquare::Empty, ChessSquare::Empty, ChessSquare::Empty, ChessSquare::Empty, ChessSquare::Empty, ChessSquare::Empty],
+      [ChessSquare::Empty, ChessSquare::Empty, ChessSquare::Empty, ChessSquare::Empty, ChessSquare::Empty, ChessSquare::Empty, ChessSquare::Empty, ChessSquare::Empty],
+      [ChessSquare::Empty, ChessSquare::Empty, ChessSquare::Empty, ChessSquare::Empty, ChessSquare::Empty, ChessSquare::Empty, ChessSquare::Empty, ChessSquare::Empty],
+    ];
+    for square in 0..64 {
+      squares[square / 8][square % 8] = get_piece_for_index(self.get_piece_at(square));
+    }
+    let mut available_castling = HashSet::new();
+    for castling in [Castling::WhiteShort, Castling::WhiteLong, Castling::BlackShort, Castling::BlackLong] {
+      if self.castling & get_castling_bit(&castling) != 0 {
+        available_castling.insert(castling);
+      }
+    }
+    let current_player = if self.side_to_move == WHITE { ChessColor::White } else { ChessColor::Black };
+    let mut board = ChessBoard{
+      last_move: None,
+      squares: squares,
+      move_number: (self.fullmove_number - 1) * 2 + self.side_to_move as u32,
+      current_player: current_player,
+      available_castling: available_castling,
+      en_passant: self.en_passant.map(|ep| (ep as usize % 8, ep as usize / 8)),
+      halfmove_clock: self.halfmove_clock,
+      fullmove_number: self.fullmove_number,
+      hash: 0,
+    };
+    board.hash = board.compute_hash();
+    return board;
+  }
+
+  // zobrist hash from scratch, matching ChessBoard::compute_hash for the same position
+  pub fn compute_hash(&self) -> u64 {
+    let mut hash = 0;
+    for piece in 0..12 {
+      let mut bits = self.pieces[piece];
+      while bits != 0 {
+        let square = bits.trailing_zeros() as usize;
+        bits &= bits - 1;
+        hash ^= ZOBRIST_KEYS.pieces[piece][square];
+      }
+    }
+    if self.side_to_move == BLACK {
+      hash ^= ZOBRIST_KEYS.black_to_move;
+    }
+    return hash ^ get_castling_bits_key(self.castling) ^ self.get_en_passant_key();
+  }
+
+  pub fn compute_pawn_hash(&self) -> u64 {
+    let mut hash = 0;
+    for piece in [PAWN as usize, 6 + PAWN as usize] {
+      let mut bits = self.pieces[piece];
+      while bits != 0 {
+        let square = bits.trailing_zeros() as usize;
+        bits &= bits - 1;
+        hash ^= ZOBRIST_KEYS.pieces[piece][square];
+      }
+    }
+    return hash;
+  }
+}
+
+pub fn from_chess_board(board: &ChessBoard) -> Position {
+  let mut position = Position{
+    pieces: [0; 12],
+    colors: [0; 2],
+    side_to_move: if board.current_player == ChessColor::White { WHITE } else { BLACK },
+    castling: 0,
+    en_passant: board.en_passant.map(|(x, y)| (y * 8 + x) as u8),
+    halfmove_clock: board.halfmove_clock,
+    fullmove_number: board.fullmove_number,
+    hash: 0,
+    pawn_hash: 0,
+  };
+  for y in 0..8 {
+    for x in 0..8 {
+      if board.squares[y][x] != ChessSquare::Empty {
+        position.toggle_piece(get_piece_index(&board.squares[y][x]) as u8, (y * 8 + x) as u8);
+      }
+    }
+  }
+  for castling in board.available_castling.iter() {
+    position.castling |= get_castling_bit(castling);
+  }
+  position.hash = position.compute_hash();
+  position.pawn_hash = position.compute_pawn_hash();
+  return position;
+}
@@ -1,7 +1,7 @@
-use core::panic;
 use std::collections::HashSet;
+use std::fmt;
 
-use crate::{castling::{Castling, all_castling}, chess_color::ChessColor, chess_move::{ChessMove, do_move, get_valid_moves}, chess_square::{ChessSquare}};
+use crate::{bitboard::get_castling_bit, castling::{Castling, all_castling}, chess_color::ChessColor, chess_move::{ChessMove, do_move, get_check, get_valid_moves, undo_move}, chess_square::{ChessSquare}, zobrist::{ZOBRIST_KEYS, compute_hash, get_castling_rights_key, get_piece_key}};
 
 pub struct ChessBoard {
   pub last_move: Option<ChessMove>,
@@ -9,71 +9,259 @@ pub struct ChessBoard {
   pub current_player: ChessColor,
   pub available_castling: HashSet<Castling>,
   pub move_number: u32,
+  // square a pawn skipped over with its last move, as (x, y)
+  pub en_passant: Option<(usize, usize)>,
+  pub halfmove_clock: u32,
+  pub fullmove_number: u32,
+  // zobrist hash of placement, side to move, castling rights and usable en passant file
+  pub hash: u64,
+}
+
+// everything make_move changes that can't be worked out again from the move itself; the move carries its captured piece
+#[derive(Clone)]
+pub struct MoveUndo {
+  pub chess_move: ChessMove,
+  pub last_move: Option<ChessMove>,
+  // castling rights before the move, as bitboard castling bits
+  pub castling: u8,
+  pub en_passant: Option<(usize, usize)>,
+  pub halfmove_clock: u32,
+  pub hash: u64,
+}
+
+#[derive(Eq, PartialEq, Debug, Clone, Copy)]
+pub enum FenField {
+  Placement,
+  ActiveColor,
+  Castling,
+  EnPassant,
+  HalfmoveClock,
+  FullmoveNumber,
+}
+
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub enum FenErrorKind {
+  MissingField,
+  TrailingInput,
+  UnexpectedCharacter(char),
+  RankTooLong,
+  RankTooShort,
+  WrongRankCount,
+  KingCount(ChessSquare, usize),
+  // a pawn on the first or last rank, where no pawn can stand
+  PawnOnBackRank,
+  // a castling right without the king and that rook on their starting squares
+  UnsupportedCastling(char),
+  InvalidValue,
+}
+
+// column is the 1-based character position in the fen where the problem was found
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub struct FenError {
+  pub field: FenField,
+  pub column: usize,
+  pub kind: FenErrorKind,
+}
+
+impl fmt::Display for FenError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    let field = match self.field {
+      FenField::Placement => "piece placement",
+      FenField::ActiveColor => "active color",
+      FenField::Castling => "castling availability",
+      FenField::EnPassant => "en passant target",
+      FenField::HalfmoveClock => "halfmove clock",
+      FenField::FullmoveNumber => "fullmove number",
+    };
+    return match &self.kind {
+      FenErrorKind::MissingField => write!(f, "missing {} field at column {}", field, self.column),
+      FenErrorKind::TrailingInput => write!(f, "unexpected input after {} at column {}", field, self.column),
+      FenErrorKind::UnexpectedCharacter(c) => write!(f, "unexpected '{}' in {} at column {}", c, field, self.column),
+      FenErrorKind::RankTooLong => write!(f, "rank has more than 8 squares in {} at column {}", field, self.column),
+      FenErrorKind::RankTooShort => write!(f, "rank has fewer than 8 squares in {} at column {}", field, self.column),
+      FenErrorKind::WrongRankCount => write!(f, "expected 8 ranks in {} at column {}", field, self.column),
+      FenErrorKind::KingCount(king, count) => write!(f, "expected one {} but found {} in {} at column {}", king.to_string(), count, field, self.column),
+      FenErrorKind::PawnOnBackRank => write!(f, "pawn on the first or last rank in {} at column {}", field, self.column),
+      FenErrorKind::UnsupportedCastling(c) => write!(f, "'{}' without the king and rook on their starting squares in {} at column {}", c, field, self.column),
+      FenErrorKind::InvalidValue => write!(f, "invalid {} at column {}", field, self.column),
+    };
+  }
+}
+
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub enum SanErrorKind {
+  Malformed,
+  IllegalMove,
+  AmbiguousMove(Vec<ChessMove>),
+}
+
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub struct SanError {
+  pub san: String,
+  pub kind: SanErrorKind,
+}
+
+impl fmt::Display for SanError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    return match &self.kind {
+      SanErrorKind::Malformed => write!(f, "'{}' is not a move in algebraic notation", self.san),
+      SanErrorKind::IllegalMove => write!(f, "'{}' is not a legal move in this position", self.san),
+      SanErrorKind::AmbiguousMove(candidates) if candidates.iter().all(|m| m.promotion.is_some()) => {
+        write!(f, "'{}' is ambiguous, it needs a promotion piece", self.san)
+      },
+      SanErrorKind::AmbiguousMove(candidates) => {
+        let origins: Vec<String> = candidates.iter().map(|m| get_square_name(m.x, m.y)).collect();
+        write!(f, "'{}' is ambiguous, it could be played from {}", self.san, origins.join(", "))
+      }
+    };
+  }
+}
+
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub enum UciErrorKind {
+  Malformed,
+  IllegalMove,
+}
+
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub struct UciError {
+  pub uci: String,
+  pub kind: UciErrorKind,
+}
+
+impl fmt::Display for UciError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    return match &self.kind {
+      UciErrorKind::Malformed => write!(f, "'{}' is not a move in coordinate notation", self.uci),
+      UciErrorKind::IllegalMove => write!(f, "'{}' is not a legal move in this position", self.uci),
+    };
+  }
 }
 
 impl ChessBoard {
   pub fn get_valid_moves(&self) -> Vec<ChessMove> {
-    return get_valid_moves(&self.last_move, &self.current_player, &self.squares, &self.available_castling);
+    return get_valid_moves(&self.en_passant, &self.current_player, &self.squares, &self.available_castling);
   }
 
   pub fn do_move(&self, chess_move: &ChessMove) -> ChessBoard {
-    let mut new_board = copy_board(&self.squares);
-    do_move(chess_move, &mut new_board);
-    let mut new_castling = self.available_castling.clone();
+    let mut next_board = self.clone();
+    next_board.make_move(chess_move);
+    return next_board;
+  }
+
+  // plays the move on this board in place and returns what unmake_move needs to take it back
+  pub fn make_move(&mut self, chess_move: &ChessMove) -> MoveUndo {
+    let undo = MoveUndo{
+      chess_move: chess_move.clone(),
+      last_move: self.last_move.replace(chess_move.clone()),
+      castling: get_castling_bits(&self.available_castling),
+      en_passant: self.en_passant,
+      halfmove_clock: self.halfmove_clock,
+      hash: self.hash,
+    };
+    let mut hash = self.hash ^ ZOBRIST_KEYS.black_to_move ^ get_castling_rights_key(&self.available_castling) ^ get_move_hash_delta(chess_move);
+    if let Some(file) = self.get_hashed_en_passant_file() {
+      hash ^= ZOBRIST_KEYS.en_passant_file[file];
+    }
+    do_move(chess_move, &mut self.squares);
+    self.remove_castling_rights(chess_move);
+    hash ^= get_castling_rights_key(&self.available_castling);
+    let is_pawn_move = chess_move.piece == ChessSquare::WhitePawn || chess_move.piece == ChessSquare::BlackPawn;
+    self.en_passant = if is_pawn_move && (chess_move.to_y as i32 - chess_move.y as i32).abs() == 2 {
+      Some((chess_move.x, (chess_move.y + chess_move.to_y) / 2))
+    } else {
+      None
+    };
+    self.halfmove_clock = if is_pawn_move || chess_move.capture.is_some() { 0 } else { self.halfmove_clock + 1 };
+    if self.current_player == ChessColor::Black {
+      self.fullmove_number += 1;
+    }
+    self.current_player = self.current_player.get_opposite();
+    self.move_number += 1;
+    if let Some(file) = self.get_hashed_en_passant_file() {
+      hash ^= ZOBRIST_KEYS.en_passant_file[file];
+    }
+    self.hash = hash;
+    debug_assert_eq!(self.hash, self.compute_hash(), "incremental hash diverged after {}", chess_move.to_string());
+    return undo;
+  }
+
+  // takes back the move make_move returned the record for, which must be the last one made on this board
+  pub fn unmake_move(&mut self, undo: MoveUndo) {
+    undo_move(&undo.chess_move, &mut self.squares);
+    for castling in [Castling::WhiteShort, Castling::WhiteLong, Castling::BlackShort, Castling::BlackLong] {
+      if undo.castling & get_castling_bit(&castling) != 0 {
+        self.available_castling.insert(castling);
+      }
+    }
+    self.current_player = self.current_player.get_opposite();
+    if self.current_player == ChessColor::Black {
+      self.fullmove_number -= 1;
+    }
+    self.move_number -= 1;
+    self.last_move = undo.last_move;
+    self.en_passant = undo.en_passant;
+    self.halfmove_clock = undo.halfmove_clock;
+    self.hash = undo.hash;
+  }
+
+  // a king move gives up both rights, and a rook leaving or being captured on its corner gives up that side
+  fn remove_castling_rights(&mut self, chess_move: &ChessMove) {
     match chess_move.piece {
       ChessSquare::BlackKing => {
-        new_castling.remove(&Castling::BlackLong);
-        new_castling.remove(&Castling::BlackShort);
+        self.available_castling.remove(&Castling::BlackLong);
+        self.available_castling.remove(&Castling::BlackShort);
       }
       ChessSquare::WhiteKing => {
-        new_castling.remove(&Castling::WhiteLong);
-        new_castling.remove(&Castling::WhiteShort);
+        self.available_castling.remove(&Castling::WhiteLong);
+        self.available_castling.remove(&Castling::WhiteShort);
       }
       ChessSquare::BlackRook => {
         if chess_move.y == 7 && chess_move.x == 0 {
-          new_castling.remove(&Castling::BlackLong);
+          self.available_castling.remove(&Castling::BlackLong);
         } else if chess_move.y == 7 && chess_move.x == 7 {
-          new_castling.remove(&Castling::BlackShort);
+          self.available_castling.remove(&Castling::BlackShort);
         }
       }
       ChessSquare::WhiteRook => {
         if chess_move.y == 0 && chess_move.x == 0 {
-          new_castling.remove(&Castling::WhiteLong);
+          self.available_castling.remove(&Castling::WhiteLong);
         } else if chess_move.y == 0 && chess_move.x == 7 {
-          new_castling.remove(&Castling::WhiteShort);
+          self.available_castling.remove(&Castling::WhiteShort);
         }
       }
       _ => { }
     }
     match chess_move.capture.as_ref() {
-      Some(c) => {
-        match c {
-          &ChessSquare::WhiteRook => {
-            if chess_move.to_y == 0 && chess_move.to_x == 0 {
-              new_castling.remove(&Castling::WhiteLong);
-            } else if chess_move.to_y == 0 && chess_move.to_x == 7 {
-              new_castling.remove(&Castling::WhiteShort);
-            }
-          },
-          &ChessSquare::BlackRook => {
-            if chess_move.to_y == 7 && chess_move.to_x == 0 {
-              new_castling.remove(&Castling::BlackLong);
-            } else if chess_move.to_y == 7 && chess_move.to_x == 7 {
-              new_castling.remove(&Castling::BlackShort);
-            }
-          },
-          _ => {}
+      Some(&ChessSquare::WhiteRook) => {
+        if chess_move.to_y == 0 && chess_move.to_x == 0 {
+          self.available_castling.remove(&Castling::WhiteLong);
+        } else if chess_move.to_y == 0 && chess_move.to_x == 7 {
+          self.available_castling.remove(&Castling::WhiteShort);
         }
-      }
-      None => {}
+      },
+      Some(&ChessSquare::BlackRook) => {
+        if chess_move.to_y == 7 && chess_move.to_x == 0 {
+          self.available_castling.remove(&Castling::BlackLong);
+        } else if chess_move.to_y == 7 && chess_move.to_x == 7 {
+          self.available_castling.remove(&Castling::BlackShort);
+        }
+      },
+      _ => {}
     }
-    return ChessBoard{
-      last_move: Some(chess_move.clone()),
-      squares: new_board,
-      current_player: self.current_player.get_opposite(),
-      available_castling: new_castling,
-      move_number: self.move_number + 1
+  }
+
+  // zobrist hash of the position computed from scratch, which the incrementally kept hash must always equal
+  pub fn compute_hash(&self) -> u64 {
+    return compute_hash(&self.squares, &self.current_player, &self.available_castling, self.get_hashed_en_passant_file());
+  }
+
+  // the en passant file only counts towards the hash while a pawn can actually capture there
+  fn get_hashed_en_passant_file(&self) -> Option<usize> {
+    if self.has_en_passant_capturer() {
+      return self.en_passant.map(|(x, _)| x);
     }
+    return None;
   }
 
   pub fn get_forsyth_edwards_notation(&self) -> String {
@@ -106,25 +294,149 @@ impl ChessBoard {
     }
     fen.push_str(" ");
 
-    // TODO available en pessant
-    fen.push_str("-");
+    match self.en_passant {
+      Some((x, y)) => fen.push_str(&get_square_name(x, y)),
+      None => fen.push_str("-"),
+    }
     fen.push_str(" ");
-    // half move clock?
-    fen.push_str("0");
+    fen.push_str(self.halfmove_clock.to_string().as_str());
     fen.push_str(" ");
 
-    fen.push_str(self.move_number.to_string().as_str());
+    fen.push_str(self.fullmove_number.to_string().as_str());
 
     return fen;
   }
 
+  // resolves a move in standard algebraic notation against the legal moves of this position. check and
+  // annotation marks are optional, "0-0" castling and lowercase piece letters are accepted.
+  pub fn parse_san(&self, san: &str) -> Result<ChessMove, SanError> {
+    let error = |kind: SanErrorKind| -> SanError {
+      return SanError{ san: String::from(san), kind: kind };
+    };
+    let text = san.trim().trim_end_matches(|c| c == '+' || c == '#' || c == '!' || c == '?');
+    let text = text.strip_suffix("e.p.").unwrap_or(text).trim_end();
+    if text.is_empty() {
+      return Err(error(SanErrorKind::Malformed));
+    }
+
+    let valid_moves = self.get_valid_moves();
+    let castling = match text {
+      "O-O" | "0-0" | "o-o" => Some(if self.current_player == ChessColor::White { Castling::WhiteShort } else { Castling::BlackShort }),
+      "O-O-O" | "0-0-0" | "o-o-o" => Some(if self.current_player == ChessColor::White { Castling::WhiteLong } else { Castling::BlackLong }),
+      _ => None
+    };
+    if castling.is_some() {
+      return match valid_moves.into_iter().find(|m| m.castling == castling) {
+        Some(m) => Ok(m),
+        None => Err(error(SanErrorKind::IllegalMove)),
+      };
+    }
+
+    let candidates = match parse_san_parts(text, &self.current_player) {
+      Some(parts) => {
+        let mut candidates = filter_san_candidates(&valid_moves, &parts);
+        // a lowercase "b" is a pawn on the b-file unless only a bishop move fits
+        if candidates.is_empty() && text.starts_with('b') {
+          let mut bishop_text = String::from("B");
+          bishop_text.push_str(&text[1..]);
+          if let Some(bishop_parts) = parse_san_parts(&bishop_text, &self.current_player) {
+            candidates = filter_san_candidates(&valid_moves, &bishop_parts);
+          }
+        }
+        candidates
+      },
+      None => {
+        if !text.starts_with('b') {
+          return Err(error(SanErrorKind::Malformed));
+        }
+        let mut bishop_text = String::from("B");
+        bishop_text.push_str(&text[1..]);
+        match parse_san_parts(&bishop_text, &self.current_player) {
+          Some(parts) => filter_san_candidates(&valid_moves, &parts),
+          None => return Err(error(SanErrorKind::Malformed)),
+        }
+      }
+    };
+
+    return match candidates.len() {
+      0 => Err(error(SanErrorKind::IllegalMove)),
+      1 => Ok(candidates[0].clone()),
+      _ => Err(error(SanErrorKind::AmbiguousMove(candidates))),
+    };
+  }
+
+  // resolves a move in coordinate notation such as "e2e4", "e1g1" or "e7e8q" against the legal moves of this position
+  pub fn parse_uci(&self, uci: &str) -> Result<ChessMove, UciError> {
+    let error = |kind: UciErrorKind| -> UciError {
+      return UciError{ uci: String::from(uci), kind: kind };
+    };
+    let text = uci.trim();
+    if !text.is_ascii() || (text.len() != 4 && text.len() != 5) {
+      return Err(error(UciErrorKind::Malformed));
+    }
+    let (x, y) = parse_square_name(&text[0..2]).ok_or(error(UciErrorKind::Malformed))?;
+    let (to_x, to_y) = parse_square_name(&text[2..4]).ok_or(error(UciErrorKind::Malformed))?;
+    let promotion = match text.chars().nth(4) {
+      Some(c) if "nbrq".contains(c.to_ascii_lowercase()) => get_piece_for_letter(c, &self.current_player),
+      Some(_) => return Err(error(UciErrorKind::Malformed)),
+      None => None,
+    };
+    return match self.get_valid_moves().into_iter().find(|m| m.x == x && m.y == y && m.to_x == to_x && m.to_y == to_y && m.promotion == promotion) {
+      Some(m) => Ok(m),
+      None => Err(error(UciErrorKind::IllegalMove)),
+    };
+  }
+
+  pub fn is_in_check(&self) -> bool {
+    return get_check(&self.squares, &self.current_player).is_some();
+  }
+
+  // true when neither side has enough material left to ever deliver mate: bare kings, a single minor piece,
+  // or only bishops that all stand on squares of one colour
+  pub fn has_insufficient_material(&self) -> bool {
+    let mut minor_pieces = 0;
+    let mut knights = 0;
+    let mut bishop_square_colors = HashSet::new();
+    for y in 0..8 {
+      for x in 0..8 {
+        match self.squares[y][x] {
+          ChessSquare::Empty | ChessSquare::WhiteKing | ChessSquare::BlackKing => {},
+          ChessSquare::WhiteKnight | ChessSquare::BlackKnight => {
+            minor_pieces += 1;
+            knights += 1;
+          },
+          ChessSquare::WhiteBishop | ChessSquare::BlackBishop => {
+            minor_pieces += 1;
+            bishop_square_colors.insert((x + y) % 2);
+          },
+          _ => return false
+        }
+      }
+    }
+    return minor_pieces <= 1 || (knights == 0 && bishop_square_colors.len() == 1);
+  }
+
+  pub fn has_en_passant_capturer(&self) -> bool {
+    return match self.en_passant {
+      Some((x, y)) => {
+        let (pawn, pawn_y) = if self.current_player == ChessColor::White { (ChessSquare::WhitePawn, y - 1) } else { (ChessSquare::BlackPawn, y + 1) };
+        (x > 0 && self.squares[pawn_y][x - 1] == pawn) || (x < 7 && self.squares[pawn_y][x + 1] == pawn)
+      },
+      None => false
+    };
+  }
+
   pub fn clone(&self) -> ChessBoard {
     return ChessBoard{
       last_move: self.last_move.clone(),
       squares: copy_board(&self.squares),
       current_player: self.current_player.clone(),
       available_castling: self.available_castling.clone(),
-      move_number: self.move_number
+      move_number: self.move_number,
+      en_passant: self.en_passant,
+      halfmove_clock: self.halfmove_clock,
+      fullmove_number: self.fullmove_number,
+      hash: self.hash,
     }
   }
 }
@@ -169,120 +481,232 @@ fn create_empty_board() -> [[ChessSquare; 8]; 8] {
 
 
 pub fn create_new_board() -> ChessBoard {
-  return ChessBoard{
+  let mut board = ChessBoard{
     last_move: None,
     squares: create_starting_board_position(),
     current_player: ChessColor::White,
     available_castling: all_castling(),
-    move_number: 0
+    move_number: 0,
+    en_passant: None,
+    halfmove_clock: 0,
+    fullmove_number: 1,
+    hash: 0,
   };
+  board.hash = board.compute_hash();
+  return board;
 }
 
-pub fn from_forsyth_edwards_notation(fen: &String) -> ChessBoard {
-  let mut squares = create_empty_board();
+pub fn from_forsyth_edwards_notation(fen: &str) -> Result<ChessBoard, FenError> {
+  let fields = split_fen_fields(fen);
+  if fields.len() > 6 {
+    return Err(FenError{ field: FenField::FullmoveNumber, column: fields[6].0, kind: FenErrorKind::TrailingInput });
+  }
+  let field = |index: usize, name: FenField| -> Result<(usize, &str), FenError> {
+    return match fields.get(index) {
+      Some(f) => Ok(*f),
+      None => Err(FenError{ field: name, column: fen.chars().count() + 1, kind: FenErrorKind::MissingField }),
+    };
+  };
+
+  let (placement_column, placement) = field(0, FenField::Placement)?;
+  let squares = parse_fen_board_part(placement, placement_column)?;
+
+  let (color_column, color) = field(1, FenField::ActiveColor)?;
+  let current_player = match color {
+    "w" => ChessColor::White,
+    "b" => ChessColor::Black,
+    _ => return Err(FenError{ field: FenField::ActiveColor, column: color_column, kind: FenErrorKind::InvalidValue }),
+  };
+
+  let (castling_column, castling_part) = field(2, FenField::Castling)?;
+  let mut castling = HashSet::new();
+  if castling_part != "-" {
+    for (i, c) in castling_part.chars().enumerate() {
+      // the row, rook file, king and rook each right needs
+      let (right, y, rook_x, king, rook) = match c {
+        'K' => (Castling::WhiteShort, 0, 7, ChessSquare::WhiteKing, ChessSquare::WhiteRook),
+        'Q' => (Castling::WhiteLong, 0, 0, ChessSquare::WhiteKing, ChessSquare::WhiteRook),
+        'k' => (Castling::BlackShort, 7, 7, ChessSquare::BlackKing, ChessSquare::BlackRook),
+        'q' => (Castling::BlackLong, 7, 0, ChessSquare::BlackKing, ChessSquare::BlackRook),
+        _ => return Err(FenError{ field: FenField::Castling, column: castling_column + i, kind: FenErrorKind::UnexpectedCharacter(c) }),
+      };
+      // the move generators take a right to mean the pieces are in place
+      if squares[y][4] != king || squares[y][rook_x] != rook {
+        return Err(FenError{ field: FenField::Castling, column: castling_column + i, kind: FenErrorKind::UnsupportedCastling(c) });
+      }
+      if !castling.insert(right) {
+        return Err(FenError{ field: FenField::Castling, column: castling_column + i, kind: FenErrorKind::UnexpectedCharacter(c) });
+      }
+    }
+  }
 
-  let fen_parts : Vec<&str> = fen.split(' ').collect();
+  let (en_passant_column, en_passant_part) = field(3, FenField::EnPassant)?;
+  let en_passant = if en_passant_part == "-" {
+    None
+  } else {
+    // the target is the square a pawn of the side that just moved skipped over, so that pawn is on the square
+    // in front of it and both the target and the square the pawn came from are empty
+    let (expected_rank, forward, pawn) = if current_player == ChessColor::White { (5, -1, ChessSquare::BlackPawn) } else { (2, 1, ChessSquare::WhitePawn) };
+    match parse_square_name(en_passant_part) {
+      Some((x, y)) if y == expected_rank
+        && squares[(y as i32 + forward) as usize][x] == pawn
+        && squares[y][x] == ChessSquare::Empty
+        && squares[(y as i32 - forward) as usize][x] == ChessSquare::Empty => Some((x, y)),
+      _ => return Err(FenError{ field: FenField::EnPassant, column: en_passant_column, kind: FenErrorKind::InvalidValue }),
+    }
+  };
+
+  // Plenty of tools emit EPD-style FENs without the two move counters, so those default to a fresh clock.
+  let halfmove_clock = match fields.get(4) {
+    Some((column, part)) => parse_fen_number(part, *column, FenField::HalfmoveClock)?,
+    None => 0,
+  };
+  let fullmove_number = match fields.get(5) {
+    Some((column, part)) => parse_fen_number(part, *column, FenField::FullmoveNumber)?,
+    None => 1,
+  };
+  if fullmove_number == 0 {
+    return Err(FenError{ field: FenField::FullmoveNumber, column: fields[5].0, kind: FenErrorKind::InvalidValue });
+  }
+
+  let mut board = ChessBoard{
+    last_move: None,
+    squares: squares,
+    current_player: current_player.clone(),
+    available_castling: castling,
+    move_number: (fullmove_number - 1) * 2 + if current_player == ChessColor::Black { 1 } else { 0 },
+    en_passant: en_passant,
+    halfmove_clock: halfmove_clock,
+    fullmove_number: fullmove_number,
+    hash: 0,
+  };
+  board.hash = board.compute_hash();
+  return Ok(board);
+}
 
-  let mut row = 0;
+// splits a fen on whitespace, keeping the 1-based column each field starts at
+fn split_fen_fields(fen: &str) -> Vec<(usize, &str)> {
+  let mut fields = Vec::new();
+  let mut start: Option<(usize, usize)> = None;
+  for (column, (byte, c)) in fen.char_indices().enumerate() {
+    if c.is_whitespace() {
+      if let Some((start_column, start_byte)) = start {
+        fields.push((start_column, &fen[start_byte..byte]));
+        start = None;
+      }
+    } else if start.is_none() {
+      start = Some((column + 1, byte));
+    }
+  }
+  if let Some((start_column, start_byte)) = start {
+    fields.push((start_column, &fen[start_byte..]));
+  }
+  return fields;
+}
+
+fn parse_fen_board_part(placement: &str, start_column: usize) -> Result<[[ChessSquare; 8]; 8], FenError> {
+  let mut squares = create_empty_board();
+  let error = |offset: usize, kind: FenErrorKind| -> FenError {
+    return FenError{ field: FenField::Placement, column: start_column + offset, kind: kind };
+  };
+
+  // fen lists rank 8 first, while squares[0] is white's back rank
+  let mut rank = 0;
   let mut col = 0;
-  for c in fen_parts.get(0).unwrap().chars() {
+  for (i, c) in placement.chars().enumerate() {
     match c {
-      'r' => {
-        squares[row][col] = ChessSquare::BlackRook;
-        col += 1;
-      },
-      'b' => {
-        squares[row][col] = ChessSquare::BlackBishop;
-        col += 1;
-      },
-      'n' => {
-        squares[row][col] = ChessSquare::BlackKnight;
-        col += 1;
-      },
-      'q' => {
-        squares[row][col] = ChessSquare::BlackQueen;
-        col += 1;
-      },
-      'k' => {
-        squares[row][col] = ChessSquare::BlackKing;
-        col += 1;
-      },
-      'p' => {
-        squares[row][col] = ChessSquare::BlackPawn;
-        col += 1;
-      },
-      
-      'R' => {
-        squares[row][col] = ChessSquare::WhiteRook;
-        col += 1;
-      },
-      'B' => {
-        squares[row][col] = ChessSquare::WhiteBishop;
-        col += 1;
-      },
-      'N' => {
-        squares[row][col] = ChessSquare::WhiteKnight;
-        col += 1;
-      },
-      'Q' => {
-        squares[row][col] = ChessSquare::WhiteQueen;
-        col += 1;
-      },
-      'K' => {
-        squares[row][col] = ChessSquare::WhiteKing;
-        col += 1;
-      },
-      'P' => {
-        squares[row][col] = ChessSquare::WhitePawn;
-        col += 1;
-      },
-
       '/' => {
-        row += 1;
         if col != 8 {
-          panic!("wtf");
+          return Err(error(i, FenErrorKind::RankTooShort));
+        }
+        rank += 1;
+        if rank > 7 {
+          return Err(error(i, FenErrorKind::WrongRankCount));
         }
         col = 0;
       },
-      
-      _ => {
+      '1'..='8' => {
         col += c.to_digit(10).unwrap() as usize;
+        if col > 8 {
+          return Err(error(i, FenErrorKind::RankTooLong));
+        }
+      },
+      _ => {
+        let piece = match get_square_from_char(c) {
+          Some(p) => p,
+          None => return Err(error(i, FenErrorKind::UnexpectedCharacter(c))),
+        };
+        if col > 7 {
+          return Err(error(i, FenErrorKind::RankTooLong));
+        }
+        if (piece == ChessSquare::WhitePawn || piece == ChessSquare::BlackPawn) && (rank == 0 || rank == 7) {
+          return Err(error(i, FenErrorKind::PawnOnBackRank));
+        }
+        squares[7 - rank][col] = piece;
+        col += 1;
       }
     }
   }
+  let end = placement.chars().count();
+  if col != 8 {
+    return Err(error(end, FenErrorKind::RankTooShort));
+  }
+  if rank != 7 {
+    return Err(error(end, FenErrorKind::WrongRankCount));
+  }
 
-  print!("{}", fen_parts.get(1).unwrap());
-  let current_player = if fen_parts[1] == "w" { ChessColor::White } else { ChessColor::Black };
-
-  let mut castling = HashSet::new();
-  for c in fen_parts[2].chars() {
-    match c {
-      'K' => {
-        castling.insert(Castling::WhiteShort);
-      },
-      'Q' => {
-        castling.insert(Castling::WhiteLong);
-      },
-      'k' => {
-        castling.insert(Castling::BlackShort);
-      },
-      'q' => {
-        castling.insert(Castling::BlackLong);
-      },
-      '-' => {}
-      _ => panic!("invalid castle char")
+  for king in [ChessSquare::WhiteKing, ChessSquare::BlackKing] {
+    let count = squares.iter().flatten().filter(|s| **s == king).count();
+    if count != 1 {
+      return Err(error(0, FenErrorKind::KingCount(king, count)));
     }
   }
+  return Ok(squares);
+}
 
-  return ChessBoard{
-    last_move: None,
-    squares: squares,
-    current_player: current_player,
-    available_castling: castling,
-    move_number: fen_parts[5].parse::<u32>().unwrap()
+fn parse_fen_number(part: &str, column: usize, field: FenField) -> Result<u32, FenError> {
+  if !part.chars().all(|c| c.is_ascii_digit()) {
+    return Err(FenError{ field: field, column: column, kind: FenErrorKind::InvalidValue });
+  }
+  return part.parse::<u32>().map_err(|_| FenError{ field: field, column: column, kind: FenErrorKind::InvalidValue });
+}
+
+fn get_square_from_char(c: char) -> Option<ChessSquare> {
+  return match c {
+    'r' => Some(ChessSquare::BlackRook),
+    'b' => Some(ChessSquare::BlackBishop),
+    'n' => Some(ChessSquare::BlackKnight),
+    'q' => Some(ChessSquare::BlackQueen),
+    'k' => Some(ChessSquare::BlackKing),
+    'p' => Some(ChessSquare::BlackPawn),
+
+    'R' => Some(ChessSquare::WhiteRook),
+    'B' => Some(ChessSquare::WhiteBishop),
+    'N' => Some(ChessSquare::WhiteKnight),
+    'Q' => Some(ChessSquare::WhiteQueen),
+    'K' => Some(ChessSquare::WhiteKing),
+    'P' => Some(ChessSquare::WhitePawn),
+    _ => None
   };
 }
 
+// x and y are board coordinates, so (4, 2) is "e3"
+pub fn get_square_name(x: usize, y: usize) -> String {
+  return [
+    String::from("abcdefgh".chars().nth(x).unwrap()),
+    (y + 1).to_string() ].join("");
+}
+
+pub fn parse_square_name(name: &str) -> Option<(usize, usize)> {
+  let mut chars = name.chars();
+  let file = chars.next()?;
+  let rank = chars.next()?;
+  if chars.next().is_some() || !('a'..='h').contains(&file) || !('1'..='8').contains(&rank) {
+    return None;
+  }
+  return Some((file as usize - 'a' as usize, rank as usize - '1' as usize));
+}
+
 pub fn get_fen_board_part(board: &[[ChessSquare; 8]; 8]) -> String {
   let mut fen = String::new();
   for y in 0..8 {
@@ -309,4 +733,334 @@ pub fn get_fen_board_part(board: &[[ChessSquare; 8]; 8]) -> String {
     }
   }
   return fen;
-}
\ No newline at end of file
+}
+
+struct SanParts {
+  piece: ChessSquare,
+  from_x: Option<usize>,
+  from_y: Option<usize>,
+  to_x: usize,
+  to_y: usize,
+  promotion: Option<ChessSquare>,
+}
+
+fn get_piece_for_letter(letter: char, color: &ChessColor) -> Option<ChessSquare> {
+  let white = match letter.to_ascii_uppercase() {
+    'P' => ChessSquare::WhitePawn,
+    'N' => ChessSquare::WhiteKnight,
+    'B' => ChessSquare::WhiteBishop,
+    'R' => ChessSquare::WhiteRook,
+    'Q' => ChessSquare::WhiteQueen,
+    'K' => ChessSquare::WhiteKing,
+    _ => return None
+  };
+  if color == &ChessColor::White {
+    return Some(white);
+  }
+  return match white {
+    ChessSquare::WhitePawn => Some(ChessSquare::BlackPawn),
+    ChessSquare::WhiteKnight => Some(ChessSquare::BlackKnight),
+    ChessSquare::WhiteBishop => Some(ChessSquare::BlackBishop),
+    ChessSquare::WhiteRook => Some(ChessSquare::BlackRook),
+    ChessSquare::WhiteQueen => Some(ChessSquare::BlackQueen),
+    _ => Some(ChessSquare::BlackKing),
+  };
+}
+
+// splits san like "Nbd7", "exd5" or "e8=N" into its parts, without checking it against the board
+fn parse_san_parts(text: &str, color: &ChessColor) -> Option<SanParts> {
+  let mut chars: Vec<char> = text.chars().filter(|c| *c != 'x' && *c != 'X' && *c != ':' && *c != '-').collect();
+
+  let mut promotion = None;
+  if chars.len() > 2 && chars[chars.len() - 1] == ')' {
+    chars.pop();
+    let letter = chars.pop()?;
+    if chars.pop()? != '(' {
+      return None;
+    }
+    promotion = Some(get_piece_for_letter(letter, color)?);
+  } else if chars.len() > 2 && chars[chars.len() - 1].is_ascii_alphabetic() {
+    let letter = chars.pop()?;
+    if chars[chars.len() - 1] == '=' {
+      chars.pop();
+    }
+    promotion = Some(get_piece_for_letter(letter, color)?);
+  }
+  if promotion == Some(ChessSquare::WhitePawn) || promotion == Some(ChessSquare::BlackPawn) {
+    return None;
+  }
+
+  let piece = match chars.first() {
+    Some(c) if "NBRQKnrqk".contains(*c) => {
+      let piece = get_piece_for_letter(*c, color)?;
+      chars.remove(0);
+      piece
+    },
+    Some(c) if *c == 'P' => {
+      chars.remove(0);
+      get_piece_for_letter('P', color)?
+    },
+    _ => get_piece_for_letter('P', color)?
+  };
+
+  if chars.len() < 2 || chars.len() > 4 {
+    return None;
+  }
+  let destination: String = chars[chars.len() - 2..].iter().collect();
+  let (to_x, to_y) = parse_square_name(&destination)?;
+  let mut from_x = None;
+  let mut from_y = None;
+  for c in chars[..chars.len() - 2].iter() {
+    match c {
+      'a'..='h' if from_x.is_none() && from_y.is_none() => from_x = Some(*c as usize - 'a' as usize),
+      '1'..='8' if from_y.is_none() => from_y = Some(*c as usize - '1' as usize),
+      _ => return None
+    }
+  }
+  return Some(SanParts{
+    piece: piece,
+    from_x: from_x,
+    from_y: from_y,
+    to_x: to_x,
+    to_y: to_y,
+    promotion: promotion,
+  });
+}
+
+fn filter_san_candidates(valid_moves: &Vec<ChessMove>, parts: &SanParts) -> Vec<ChessMove> {
+  return valid_moves.iter().filter(|m| {
+    return m.castling.is_none()
+      && m.piece == parts.piece
+      && m.to_x == parts.to_x
+      && m.to_y == parts.to_y
+      && parts.from_x.is_none_or(|x| x == m.x)
+      && parts.from_y.is_none_or(|y| y == m.y)
+      && (parts.promotion.is_none() || m.promotion == parts.promotion);
+  }).cloned().collect();
+}
+
+// xor of the piece-square keys a move changes, for keeping the hash up to date without rescanning the board
+pub fn get_move_hash_delta(chess_move: &ChessMove) -> u64 {
+  return match &chess_move.castling {
+    Some(castling) => {
+      let (king, rook, y, rook_x, king_to_x, rook_to_x) = match castling {
+        Castling::WhiteShort => (ChessSquare::WhiteKing, ChessSquare::WhiteRook, 0, 7, 6, 5),
+        Castling::WhiteLong => (ChessSquare::WhiteKing, ChessSquare::WhiteRook, 0, 0, 2, 3),
+        Castling::BlackShort => (ChessSquare::BlackKing, ChessSquare::BlackRook, 7, 7, 6, 5),
+        Castling::BlackLong => (ChessSquare::BlackKing, ChessSquare::BlackRook, 7, 0, 2, 3),
+      };
+      get_piece_key(&king, 4, y) ^ get_piece_key(&king, king_to_x, y) ^ get_piece_key(&rook, rook_x, y) ^ get_piece_key(&rook, rook_to_x, y)
+    },
+    None => {
+      let mut delta = get_piece_key(&chess_move.piece, chess_move.x, chess_move.y);
+      delta ^= get_piece_key(chess_move.promotion.as_ref().unwrap_or(&chess_move.piece), chess_move.to_x, chess_move.to_y);
+      match &chess_move.capture {
+        Some(captured) if chess_move.en_pessant => delta ^= get_piece_key(captured, chess_move.to_x, chess_move.y),
+        Some(captured) => delta ^= get_piece_key(captured, chess_move.to_x, chess_move.to_y),
+        None => {}
+      }
+      delta
+    }
+  };
+}
+
+pub fn get_castling_bits(available_castling: &HashSet<Castling>) -> u8 {
+  return available_castling.iter().fold(0, |bits, c| bits | get_castling_bit(c));
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::castling::Castling;
+  use crate::chess_square::ChessSquare;
+  use super::{FenError, FenErrorKind, FenField, SanErrorKind, UciErrorKind, create_new_board, from_forsyth_edwards_notation};
+
+  fn error(fen: &str) -> FenError {
+    return from_forsyth_edwards_notation(fen).err().unwrap();
+  }
+
+  fn check_error(fen: &str, field: FenField, column: usize, kind: FenErrorKind) {
+    assert_eq!(error(fen), FenError{ field: field, column: column, kind: kind }, "{}", fen);
+  }
+
+  #[test]
+  fn round_trips() {
+    for fen in [
+      "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+      "rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR w KQkq e6 0 2",
+      "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1",
+      "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+      "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+      "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 7 10",
+    ] {
+      assert_eq!(from_forsyth_edwards_notation(fen).unwrap().get_forsyth_edwards_notation(), fen);
+    }
+  }
+
+  #[test]
+  fn missing_counters_default() {
+    let board = from_forsyth_edwards_notation("4k3/8/8/8/8/8/8/4K3 b - -").unwrap();
+    assert_eq!(board.get_forsyth_edwards_notation(), "4k3/8/8/8/8/8/8/4K3 b - - 0 1");
+  }
+
+  #[test]
+  fn placement_errors() {
+    check_error("4k3/8/8/8/8/8/8/4K3X w - - 0 1", FenField::Placement, 20, FenErrorKind::UnexpectedCharacter('X'));
+    check_error("4k4/8/8/8/8/8/8/4K3 w - - 0 1", FenField::Placement, 3, FenErrorKind::RankTooLong);
+    check_error("4k2/8/8/8/8/8/8/4K3 w - - 0 1", FenField::Placement, 4, FenErrorKind::RankTooShort);
+    check_error("4k3/8/8/8/8/8/4K3 w - - 0 1", FenField::Placement, 18, FenErrorKind::WrongRankCount);
+    check_error("8/8/8/8/8/8/8/4K3 w - - 0 1", FenField::Placement, 1, FenErrorKind::KingCount(ChessSquare::BlackKing, 0));
+    check_error("P3k3/8/8/8/8/8/8/4K3 w - - 0 1", FenField::Placement, 1, FenErrorKind::PawnOnBackRank);
+    check_error("4k3/8/8/8/8/8/8/p3K3 b - - 0 1", FenField::Placement, 17, FenErrorKind::PawnOnBackRank);
+  }
+
+  #[test]
+  fn field_errors() {
+    check_error("4k3/8/8/8/8/8/8/4K3", FenField::ActiveColor, 20, FenErrorKind::MissingField);
+    check_error("4k3/8/8/8/8/8/8/4K3 x - - 0 1", FenField::ActiveColor, 21, FenErrorKind::InvalidValue);
+    check_error("4k3/8/8/8/8/8/8/4K3 w X - 0 1", FenField::Castling, 23, FenErrorKind::UnexpectedCharacter('X'));
+    check_error("r3k2r/8/8/8/8/8/8/R3K2R w KQkk - 0 1", FenField::Castling, 30, FenErrorKind::UnexpectedCharacter('k'));
+    check_error("4k3/8/8/8/8/8/8/4K3 w - e9 0 1", FenField::EnPassant, 25, FenErrorKind::InvalidValue);
+    check_error("4k3/8/8/8/8/8/8/4K3 w - - x 1", FenField::HalfmoveClock, 27, FenErrorKind::InvalidValue);
+    check_error("4k3/8/8/8/8/8/8/4K3 w - - 0 0", FenField::FullmoveNumber, 29, FenErrorKind::InvalidValue);
+    check_error("4k3/8/8/8/8/8/8/4K3 w - - 0 1 x", FenField::FullmoveNumber, 31, FenErrorKind::TrailingInput);
+  }
+
+  #[test]
+  fn castling_needs_king_and_rook() {
+    check_error("4k3/8/8/8/8/8/8/3K3R w K - 0 1", FenField::Castling, 24, FenErrorKind::UnsupportedCastling('K'));
+    check_error("4k3/8/8/8/8/8/8/4K3 w Q - 0 1", FenField::Castling, 23, FenErrorKind::UnsupportedCastling('Q'));
+    check_error("1r2k3/8/8/8/8/8/8/4K3 w q - 0 1", FenField::Castling, 25, FenErrorKind::UnsupportedCastling('q'));
+    check_error("4kr2/8/8/8/8/8/8/4K3 w k - 0 1", FenField::Castling, 24, FenErrorKind::UnsupportedCastling('k'));
+    assert!(from_forsyth_edwards_notation("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1").is_ok());
+  }
+
+  #[test]
+  fn en_passant_needs_the_pawn() {
+    check_error("4k3/8/8/8/8/8/8/4K3 b - e3 0 1", FenField::EnPassant, 25, FenErrorKind::InvalidValue);
+    // the pawn is there but so is something on the square it skipped
+    check_error("4k3/8/8/8/4P3/4N3/8/4K3 b - e3 0 1", FenField::EnPassant, 29, FenErrorKind::InvalidValue);
+    // wrong rank for the side to move
+    check_error("4k3/8/8/8/4P3/8/8/4K3 w - e3 0 1", FenField::EnPassant, 27, FenErrorKind::InvalidValue);
+    assert!(from_forsyth_edwards_notation("4k3/8/8/8/4P3/8/8/4K3 b - e3 0 1").is_ok());
+  }
+
+  #[test]
+  fn messages() {
+    assert_eq!(error("P3k3/8/8/8/8/8/8/4K3 w - - 0 1").to_string(), "pawn on the first or last rank in piece placement at column 1");
+    assert_eq!(error("4k3/8/8/8/8/8/8/3K3R w K - 0 1").to_string(), "'K' without the king and rook on their starting squares in castling availability at column 24");
+  }
+
+  fn san_error(fen: &str, san: &str) -> SanErrorKind {
+    return from_forsyth_edwards_notation(fen).unwrap().parse_san(san).unwrap_err().kind;
+  }
+
+  #[test]
+  fn san_round_trips() {
+    for fen in [
+      "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+      "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+      "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+      "R6R/6k1/8/1Q1Q4/8/1Q1Q4/8/4K3 w - - 0 1",
+    ] {
+      let board = from_forsyth_edwards_notation(fen).unwrap();
+      for m in board.get_valid_moves() {
+        assert_eq!(board.parse_san(&m.to_san(&board)), Ok(m.clone()), "{} in {}", m.to_san(&board), fen);
+      }
+    }
+  }
+
+  #[test]
+  fn san_disambiguation() {
+    // knights on b1 and f1 both reach d2, rooks on a1 and a5 both reach a3
+    let fen = "4k3/8/8/R7/8/8/8/RN2KN2 w - - 0 1";
+    let board = from_forsyth_edwards_notation(fen).unwrap();
+    match san_error(fen, "Nd2") {
+      SanErrorKind::AmbiguousMove(candidates) => assert_eq!(candidates.len(), 2),
+      kind => panic!("{:?}", kind),
+    }
+    assert_eq!(board.parse_san("Nbd2").unwrap().x, 1);
+    assert_eq!(board.parse_san("Nfd2").unwrap().x, 5);
+    assert_eq!(board.parse_san("Nf1d2").unwrap().x, 5);
+    assert!(matches!(san_error(fen, "Ra3"), SanErrorKind::AmbiguousMove(_)));
+    assert_eq!(board.parse_san("R1a3").unwrap().y, 0);
+    assert_eq!(board.parse_san("R5a3").unwrap().y, 4);
+    // a disambiguation that matches neither piece
+    assert_eq!(san_error(fen, "Ncd2"), SanErrorKind::IllegalMove);
+  }
+
+  #[test]
+  fn san_promotion() {
+    let fen = "r3k3/1P6/8/8/8/8/8/4K3 w - - 0 1";
+    let board = from_forsyth_edwards_notation(fen).unwrap();
+    for (san, piece) in [("b8=Q", ChessSquare::WhiteQueen), ("b8N", ChessSquare::WhiteKnight), ("b8(R)", ChessSquare::WhiteRook), ("bxa8=B+", ChessSquare::WhiteBishop), ("ba8q", ChessSquare::WhiteQueen)] {
+      assert_eq!(board.parse_san(san).unwrap().promotion, Some(piece), "{}", san);
+    }
+    assert!(matches!(san_error(fen, "b8"), SanErrorKind::AmbiguousMove(_)));
+    assert_eq!(san_error(fen, "b8=K"), SanErrorKind::IllegalMove);
+    assert_eq!(san_error(fen, "b8=P"), SanErrorKind::Malformed);
+  }
+
+  #[test]
+  fn san_notation_variants() {
+    let board = from_forsyth_edwards_notation("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1").unwrap();
+    assert_eq!(board.parse_san("O-O").unwrap().to_san(&board), "O-O");
+    assert_eq!(board.parse_san("0-0-0+").unwrap().to_san(&board), "O-O-O");
+    let board = create_new_board();
+    assert_eq!(board.parse_san(" e4!? ").unwrap().to_san(&board), "e4");
+    assert_eq!(board.parse_san("nf3").unwrap().to_san(&board), "Nf3");
+    assert_eq!(board.parse_san("Pe2-e4").unwrap().to_san(&board), "e4");
+    assert_eq!(board.parse_san("O-O").unwrap_err().kind, SanErrorKind::IllegalMove);
+  }
+
+  #[test]
+  fn san_messages() {
+    let board = create_new_board();
+    assert_eq!(board.parse_san("Zz9").unwrap_err().to_string(), "'Zz9' is not a move in algebraic notation");
+    assert_eq!(board.parse_san("").unwrap_err().to_string(), "'' is not a move in algebraic notation");
+    assert_eq!(board.parse_san("e5").unwrap_err().to_string(), "'e5' is not a legal move in this position");
+    let board = from_forsyth_edwards_notation("4k3/8/8/8/8/8/8/1N2KN2 w - - 0 1").unwrap();
+    assert_eq!(board.parse_san("Nd2").unwrap_err().to_string(), "'Nd2' is ambiguous, it could be played from b1, f1");
+    let board = from_forsyth_edwards_notation("4k3/1P6/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+    assert_eq!(board.parse_san("b8").unwrap_err().to_string(), "'b8' is ambiguous, it needs a promotion piece");
+  }
+
+  #[test]
+  fn uci_castling() {
+    let board = from_forsyth_edwards_notation("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1").unwrap();
+    let m = board.parse_uci("e1g1").unwrap();
+    assert_eq!(m.castling, Some(Castling::WhiteShort));
+    assert_eq!(m.to_uci(), "e1g1");
+    let board = from_forsyth_edwards_notation("r3k2r/8/8/8/8/8/8/R3K2R b KQkq - 0 1").unwrap();
+    let m = board.parse_uci("e8c8").unwrap();
+    assert_eq!(m.castling, Some(Castling::BlackLong));
+    assert_eq!(m.to_uci(), "e8c8");
+  }
+
+  #[test]
+  fn uci_promotion() {
+    let board = from_forsyth_edwards_notation("k7/4P3/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+    assert_eq!(board.parse_uci("e7e8q").unwrap().promotion, Some(ChessSquare::WhiteQueen));
+    assert_eq!(board.parse_uci("e7e8n").unwrap().promotion, Some(ChessSquare::WhiteKnight));
+    assert_eq!(board.parse_uci("e7e8n").unwrap().to_uci(), "e7e8n");
+    // a promotion needs its piece
+    assert_eq!(board.parse_uci("e7e8").unwrap_err().kind, UciErrorKind::IllegalMove);
+  }
+
+  #[test]
+  fn uci_round_trips() {
+    let board = from_forsyth_edwards_notation("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1").unwrap();
+    for m in board.get_valid_moves() {
+      assert_eq!(board.parse_uci(&m.to_uci()), Ok(m.clone()), "{}", m.to_uci());
+    }
+  }
+
+  #[test]
+  fn uci_errors() {
+    let board = create_new_board();
+    for uci in ["e2e", "e2e4k", "z9a1"] {
+      assert_eq!(board.parse_uci(uci).unwrap_err().kind, UciErrorKind::Malformed, "{}", uci);
+    }
+    assert_eq!(board.parse_uci("e2e5").unwrap_err().kind, UciErrorKind::IllegalMove);
+    assert_eq!(board.parse_uci("e2e5").unwrap_err().to_string(), "'e2e5' is not a legal move in this position");
+  }
+}
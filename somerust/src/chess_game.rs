@@ -0,0 +1,240 @@
+use crate::{chess_board::{ChessBoard, MoveUndo}, chess_color::ChessColor, chess_move::ChessMove};
+
+#[derive(Hash, Eq, PartialEq, Debug, Clone)]
+pub enum DrawReason {
+  Stalemate,
+  InsufficientMaterial,
+  // claimable by either player
+  FiftyMoveRule,
+  ThreefoldRepetition,
+  // applied by the arbiter without a claim
+  SeventyFiveMoveRule,
+  FivefoldRepetition,
+}
+
+#[derive(Hash, Eq, PartialEq, Debug, Clone)]
+pub enum GameOutcome {
+  // the color that delivered mate
+  Checkmate(ChessColor),
+  Draw(DrawReason),
+}
+
+impl GameOutcome {
+  pub fn get_pgn_result(&self) -> String {
+    return match self {
+      GameOutcome::Checkmate(ChessColor::White) => String::from("1-0"),
+      GameOutcome::Checkmate(ChessColor::Black) => String::from("0-1"),
+      GameOutcome::Draw(_) => String::from("1/2-1/2"),
+    };
+  }
+
+  pub fn to_string(&self) -> String {
+    return match self {
+      GameOutcome::Checkmate(ChessColor::White) => String::from("white wins by checkmate"),
+      GameOutcome::Checkmate(ChessColor::Black) => String::from("black wins by checkmate"),
+      GameOutcome::Draw(DrawReason::Stalemate) => String::from("draw by stalemate"),
+      GameOutcome::Draw(DrawReason::InsufficientMaterial) => String::from("draw by insufficient material"),
+      GameOutcome::Draw(DrawReason::FiftyMoveRule) => String::from("draw by the fifty-move rule"),
+      GameOutcome::Draw(DrawReason::ThreefoldRepetition) => String::from("draw by threefold repetition"),
+      GameOutcome::Draw(DrawReason::SeventyFiveMoveRule) => String::from("draw by the seventy-five-move rule"),
+      GameOutcome::Draw(DrawReason::FivefoldRepetition) => String::from("draw by fivefold repetition"),
+    };
+  }
+}
+
+// A board together with the moves that led to it, which is what repetition and the move-count rules need.
+pub struct ChessGame {
+  pub starting_position: ChessBoard,
+  pub board: ChessBoard,
+  pub moves: Vec<ChessMove>,
+  // one record per move, for taking moves back in place
+  undo_records: Vec<MoveUndo>,
+  // hashes of every board in the game, starting position first
+  position_keys: Vec<u64>,
+}
+
+impl ChessGame {
+  pub fn clone(&self) -> ChessGame {
+    return ChessGame{
+      starting_position: self.starting_position.clone(),
+      board: self.board.clone(),
+      moves: self.moves.clone(),
+      undo_records: self.undo_records.clone(),
+      position_keys: self.position_keys.clone(),
+    };
+  }
+
+  pub fn do_move(&mut self, chess_move: &ChessMove) {
+    self.undo_records.push(self.board.make_move(chess_move));
+    self.position_keys.push(self.board.hash);
+    self.moves.push(chess_move.clone());
+  }
+
+  pub fn undo_move(&mut self) -> Option<ChessMove> {
+    let undo = self.undo_records.pop()?;
+    self.board.unmake_move(undo);
+    self.position_keys.pop();
+    return self.moves.pop();
+  }
+
+  // how many times the current position has occurred, counting this occurrence
+  pub fn get_repetition_count(&self) -> usize {
+    let current = self.position_keys.last().unwrap();
+    return self.get_reversible_keys().iter().filter(|k| *k == current).count();
+  }
+
+  // keys of the positions since the last capture or pawn move, the only ones that can repeat the current one
+  pub fn get_reversible_keys(&self) -> &[u64] {
+    let reversible = (self.board.halfmove_clock as usize + 1).min(self.position_keys.len());
+    return &self.position_keys[self.position_keys.len() - reversible..];
+  }
+
+  // the outcome once the game is over by rule, without any player needing to claim it
+  pub fn get_outcome(&self) -> Option<GameOutcome> {
+    if self.board.get_valid_moves().is_empty() {
+      return Some(if self.board.is_in_check() {
+        GameOutcome::Checkmate(self.board.current_player.get_opposite())
+      } else {
+        GameOutcome::Draw(DrawReason::Stalemate)
+      });
+    }
+    if self.board.has_insufficient_material() {
+      return Some(GameOutcome::Draw(DrawReason::InsufficientMaterial));
+    }
+    if self.get_repetition_count() >= 5 {
+      return Some(GameOutcome::Draw(DrawReason::FivefoldRepetition));
+    }
+    if self.board.halfmove_clock >= 150 {
+      return Some(GameOutcome::Draw(DrawReason::SeventyFiveMoveRule));
+    }
+    return None;
+  }
+
+  // a draw either player may claim in the current position
+  pub fn get_claimable_draw(&self) -> Option<DrawReason> {
+    if self.get_repetition_count() >= 3 {
+      return Some(DrawReason::ThreefoldRepetition);
+    }
+    if self.board.halfmove_clock >= 100 {
+      return Some(DrawReason::FiftyMoveRule);
+    }
+    return None;
+  }
+}
+
+pub fn create_game(starting_position: ChessBoard) -> ChessGame {
+  return ChessGame{
+    board: starting_position.clone(),
+    position_keys: vec![starting_position.hash],
+    starting_position: starting_position,
+    moves: Vec::new(),
+    undo_records: Vec::new(),
+  };
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::chess_board::{create_new_board, from_forsyth_edwards_notation};
+  use crate::chess_color::ChessColor;
+  use super::{ChessGame, DrawReason, GameOutcome, create_game};
+
+  fn play(game: &mut ChessGame, moves: &str) {
+    for san in moves.split_whitespace() {
+      let chess_move = game.board.parse_san(san).unwrap();
+      game.do_move(&chess_move);
+    }
+  }
+
+  fn create_game_from(fen: &str) -> ChessGame {
+    return create_game(from_forsyth_edwards_notation(fen).unwrap());
+  }
+
+  #[test]
+  fn repetition() {
+    let mut game = create_game(create_new_board());
+    play(&mut game, "Nf3 Nf6 Ng1 Ng8");
+    assert_eq!(game.get_repetition_count(), 2);
+    assert_eq!(game.get_claimable_draw(), None);
+    play(&mut game, "Nf3 Nf6 Ng1 Ng8");
+    assert_eq!(game.get_repetition_count(), 3);
+    assert_eq!(game.get_claimable_draw(), Some(DrawReason::ThreefoldRepetition));
+    assert_eq!(game.get_outcome(), None);
+    play(&mut game, "Nf3 Nf6 Ng1 Ng8 Nf3 Nf6 Ng1");
+    assert_eq!(game.get_outcome(), None);
+    play(&mut game, "Ng8");
+    assert_eq!(game.get_repetition_count(), 5);
+    assert_eq!(game.get_outcome(), Some(GameOutcome::Draw(DrawReason::FivefoldRepetition)));
+    // taking the last move back takes the repetition with it
+    game.undo_move();
+    assert_eq!(game.get_outcome(), None);
+  }
+
+  #[test]
+  fn repetition_needs_the_same_rights() {
+    let mut game = create_game(create_new_board());
+    play(&mut game, "e4 e5 Ke2 Ke7 Ke1 Ke8");
+    // the same pieces on the same squares, but neither side can castle any more
+    assert_eq!(game.get_repetition_count(), 1);
+    play(&mut game, "Ke2 Ke7 Ke1 Ke8");
+    assert_eq!(game.get_repetition_count(), 2);
+    // a pawn move means nothing before it can come up again
+    play(&mut game, "d3");
+    assert_eq!(game.get_reversible_keys().len(), 1);
+  }
+
+  #[test]
+  fn move_count_rules() {
+    let mut game = create_game_from("4k3/8/8/8/8/8/4P3/R3K3 w - - 99 80");
+    assert_eq!(game.get_claimable_draw(), None);
+    play(&mut game, "Ra2");
+    assert_eq!(game.get_claimable_draw(), Some(DrawReason::FiftyMoveRule));
+    assert_eq!(game.get_outcome(), None);
+    // a pawn move starts the count again
+    let mut game = create_game_from("4k3/8/8/8/8/8/4P3/R3K3 w - - 99 80");
+    play(&mut game, "e4");
+    assert_eq!(game.get_claimable_draw(), None);
+
+    let mut game = create_game_from("4k3/8/8/8/8/8/4P3/R3K3 w - - 149 80");
+    play(&mut game, "Ra2");
+    assert_eq!(game.get_outcome(), Some(GameOutcome::Draw(DrawReason::SeventyFiveMoveRule)));
+    // mate on the move that reaches the limit still counts as mate
+    let mut game = create_game_from("4k3/R7/4K3/8/8/8/8/8 w - - 149 80");
+    play(&mut game, "Ra8#");
+    assert_eq!(game.get_outcome(), Some(GameOutcome::Checkmate(ChessColor::White)));
+  }
+
+  #[test]
+  fn insufficient_material() {
+    for (fen, insufficient) in [
+      ("4k3/8/8/8/8/8/8/4K3 w - - 0 1", true),
+      ("4k3/8/8/8/8/8/8/4KN2 w - - 0 1", true),
+      ("4kb2/8/8/8/8/8/8/4K3 w - - 0 1", true),
+      // one bishop on each colour, then both on light squares
+      ("4k3/8/8/8/8/8/8/2B1KB2 w - - 0 1", false),
+      ("4k3/8/8/8/8/8/8/3BKB2 w - - 0 1", true),
+      ("2b1k3/8/8/8/8/8/8/4KB2 w - - 0 1", true),
+      ("3bk3/8/8/8/8/8/8/4KB2 w - - 0 1", false),
+      ("4k3/8/8/8/8/8/8/4KNN1 w - - 0 1", false),
+      ("4kn2/8/8/8/8/8/8/4KN2 w - - 0 1", false),
+      ("4k3/8/8/8/8/8/4P3/4K3 w - - 0 1", false),
+      ("4k3/8/8/8/8/8/8/R3K3 w - - 0 1", false),
+    ] {
+      assert_eq!(from_forsyth_edwards_notation(fen).unwrap().has_insufficient_material(), insufficient, "{}", fen);
+    }
+    let mut game = create_game_from("4k3/8/8/8/8/8/8/3rK3 w - - 0 1");
+    play(&mut game, "Kxd1");
+    assert_eq!(game.get_outcome(), Some(GameOutcome::Draw(DrawReason::InsufficientMaterial)));
+  }
+
+  #[test]
+  fn mate_and_stalemate() {
+    let mut game = create_game(create_new_board());
+    play(&mut game, "f3 e5 g4 Qh4#");
+    let outcome = game.get_outcome().unwrap();
+    assert_eq!(outcome, GameOutcome::Checkmate(ChessColor::Black));
+    assert_eq!((outcome.get_pgn_result(), outcome.to_string()), (String::from("0-1"), String::from("black wins by checkmate")));
+    let outcome = create_game_from("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1").get_outcome().unwrap();
+    assert_eq!(outcome, GameOutcome::Draw(DrawReason::Stalemate));
+    assert_eq!((outcome.get_pgn_result(), outcome.to_string()), (String::from("1/2-1/2"), String::from("draw by stalemate")));
+  }
+}
@@ -1,7 +1,7 @@
 use core::panic;
 use std::collections::HashSet;
 
-use crate::chess_board::{ChessBoard, copy_board, get_fen_board_part};
+use crate::chess_board::{ChessBoard, copy_board, get_fen_board_part, get_square_name};
 use crate::chess_color::ChessColor;
 use crate::chess_square::{ChessSquare};
 use crate::castling::Castling;
@@ -40,6 +40,81 @@ impl ChessMove {
         }
     }
   }
+
+  // standard algebraic notation for this move, played from the given board
+  pub fn to_san(&self, board: &ChessBoard) -> String {
+    let mut san = match self.castling.as_ref() {
+      Some(&Castling::BlackLong) | Some(&Castling::WhiteLong) => String::from("O-O-O"),
+      Some(&Castling::BlackShort) | Some(&Castling::WhiteShort) => String::from("O-O"),
+      None => {
+        let mut san = String::new();
+        let destination = get_square_name(self.to_x, self.to_y);
+        match &self.piece {
+          &ChessSquare::WhitePawn | &ChessSquare::BlackPawn => {
+            if self.capture.is_some() {
+              san.push_str(&get_square_name(self.x, self.y)[0..1]);
+              san.push_str("x");
+            }
+            san.push_str(&destination);
+            match &self.promotion {
+              Some(p) => {
+                san.push_str("=");
+                san.push_str(&p.get_pgn_char());
+              }
+              None => {}
+            }
+          },
+          _ => {
+            san.push_str(&self.piece.get_pgn_char());
+            san.push_str(&self.get_disambiguation(board));
+            if self.capture.is_some() {
+              san.push_str("x");
+            }
+            san.push_str(&destination);
+          }
+        }
+        san
+      }
+    };
+
+    let next_board = board.do_move(self);
+    if get_check(&next_board.squares, &next_board.current_player).is_some() {
+      if next_board.get_valid_moves().is_empty() {
+        san.push_str("#");
+      } else {
+        san.push_str("+");
+      }
+    }
+    return san;
+  }
+
+  // long algebraic coordinate notation as used by uci, castling is written as the king's move ("e1g1")
+  // and promotions get a lowercase piece suffix ("e7e8q")
+  pub fn to_uci(&self) -> String {
+    let mut uci = get_square_name(self.x, self.y);
+    uci.push_str(&get_square_name(self.to_x, self.to_y));
+    match &self.promotion {
+      Some(p) => uci.push_str(&p.get_pgn_char().to_lowercase()),
+      None => {}
+    }
+    return uci;
+  }
+
+  // the origin file, rank or square needed to tell this move apart from other legal moves of the same piece type to the same square
+  fn get_disambiguation(&self, board: &ChessBoard) -> String {
+    let rivals: Vec<ChessMove> = board.get_valid_moves().into_iter().filter(|m| {
+      return m.piece == self.piece && m.to_x == self.to_x && m.to_y == self.to_y && (m.x != self.x || m.y != self.y);
+    }).collect();
+    let origin = get_square_name(self.x, self.y);
+    if rivals.is_empty() {
+      return String::new();
+    } else if rivals.iter().all(|m| m.x != self.x) {
+      return String::from(&origin[0..1]);
+    } else if rivals.iter().all(|m| m.y != self.y) {
+      return String::from(&origin[1..2]);
+    }
+    return origin;
+  }
 }
 
 fn get_rook_directions() -> Vec<(i32, i32)> {
@@ -139,7 +214,7 @@ fn create_pawn_moves(x: usize, y: usize, to_x: usize, to_y: usize, piece: &Chess
   return pawn_moves;
 }
 
-fn potential_pawn_moves(last_move: &Option<ChessMove>, x: usize, y: usize, piece: &ChessSquare, board: &[[ChessSquare; 8]; 8]) -> Vec<ChessMove> {
+fn potential_pawn_moves(en_passant: &Option<(usize, usize)>, x: usize, y: usize, piece: &ChessSquare, board: &[[ChessSquare; 8]; 8]) -> Vec<ChessMove> {
   let mut pawn_moves: Vec<ChessMove> = Vec::new();
   let new_y  = match piece.get_color() {
     ChessColor::White => y + 1,
@@ -164,40 +239,21 @@ fn potential_pawn_moves(last_move: &Option<ChessMove>, x: usize, y: usize, piece
     pawn_moves.append(&mut create_pawn_moves(x, y, x + 1, new_y, piece, Some(board[new_y][x + 1].clone())));
   }
   // En pessant
-  if last_move.is_some() {
-    let last = last_move.as_ref().unwrap();
-    match last.piece {
-      ChessSquare::WhitePawn if clr == ChessColor::Black && new_y == 2 && last.y == 1 && last.to_y == 3 && (x as i32 - last.x as i32).abs() == 1 => {
-        pawn_moves.push(ChessMove{
-          piece: piece.clone(),
-          x: x,
-          y: y,
-          to_x: last.x,
-          to_y: 2,
-          capture: Some(ChessSquare::WhitePawn),
-          promotion: None,
-          castling: None,
-          en_pessant: true
-        });
-      },
-      ChessSquare::BlackPawn => {
-        let available = clr == ChessColor::White && new_y == 5 && last.y == 6 && last.to_y == 4 && (x as i32 - last.x as i32).abs() == 1;
-        if available {
-          pawn_moves.push(ChessMove{
-            piece: piece.clone(),
-            x: x,
-            y: y,
-            to_x: last.x,
-            to_y: 5,
-            capture: Some(ChessSquare::BlackPawn),
-            promotion: None,
-            castling: None,
-            en_pessant: true
-          });
-        }
-      },
-      _ => {}
-    }
+  match en_passant {
+    Some((ep_x, ep_y)) if *ep_y == new_y && (x as i32 - *ep_x as i32).abs() == 1 => {
+      pawn_moves.push(ChessMove{
+        piece: piece.clone(),
+        x: x,
+        y: y,
+        to_x: *ep_x,
+        to_y: *ep_y,
+        capture: Some(if clr == ChessColor::White { ChessSquare::BlackPawn } else { ChessSquare::WhitePawn }),
+        promotion: None,
+        castling: None,
+        en_pessant: true
+      });
+    },
+    _ => {}
   }
   return pawn_moves;
 }
@@ -339,7 +395,7 @@ fn get_potential_moves_for_king(player: &ChessColor, piece: &ChessSquare, x: usi
   return potential_moves;
 }
 
-fn get_potential_moves(last_move: &Option<ChessMove>, player: &ChessColor, board: &[[ChessSquare; 8]; 8], available_castling: &HashSet<Castling>) -> Vec<ChessMove> {
+fn get_potential_moves(en_passant: &Option<(usize, usize)>, player: &ChessColor, board: &[[ChessSquare; 8]; 8], available_castling: &HashSet<Castling>) -> Vec<ChessMove> {
   let mut potential_moves: Vec<ChessMove> = Vec::new();
   for y in 0..8 {
     for x in 0..8 {
@@ -347,7 +403,7 @@ fn get_potential_moves(last_move: &Option<ChessMove>, player: &ChessColor, board
       if piece != &ChessSquare::Empty && &piece.get_color() == player {
         match piece {
           ChessSquare::WhitePawn | ChessSquare::BlackPawn => {
-            potential_moves.append(&mut potential_pawn_moves(last_move, x, y, piece, board));
+            potential_moves.append(&mut potential_pawn_moves(en_passant, x, y, piece, board));
           },
           ChessSquare::WhiteRook | ChessSquare::BlackRook => {
             potential_moves.append(&mut get_potential_moves_for_directional_piece(player, piece, x, y, &get_rook_directions(), board));
@@ -379,7 +435,11 @@ pub fn do_move(chess_move: &ChessMove, board: &mut [[ChessSquare; 8]; 8]) {
       squares: copy_board(board),
       current_player: chess_move.piece.get_color(),
       available_castling: HashSet::new(),
-      move_number: 0
+      move_number: 0,
+      en_passant: None,
+      halfmove_clock: 0,
+      fullmove_number: 1,
+      hash: 0,
     }.get_forsyth_edwards_notation();
     panic!("cannot perform {} {} {} {} on board {}", chess_move.to_string(), chess_move.en_pessant, chess_move.capture.is_some(), chess_move.promotion.is_some(), fen);
   }
@@ -447,7 +507,11 @@ pub fn undo_move(chess_move: &ChessMove, board: &mut [[ChessSquare; 8]; 8]) {
       squares: copy_board(board),
       current_player: chess_move.piece.get_color(),
       available_castling: HashSet::new(),
-      move_number: 0
+      move_number: 0,
+      en_passant: None,
+      halfmove_clock: 0,
+      fullmove_number: 1,
+      hash: 0,
     }.get_forsyth_edwards_notation();
     panic!("bad state {} for move {}", fen, chess_move.to_string());
   }
@@ -474,8 +538,8 @@ pub fn undo_move(chess_move: &ChessMove, board: &mut [[ChessSquare; 8]; 8]) {
         },
         Castling::BlackShort => {
           board[7][7] = ChessSquare::BlackRook;
-          board[7][6] = ChessSquare::BlackKing;
-          board[7][5] = ChessSquare::BlackRook;
+          board[7][6] = ChessSquare::Empty;
+          board[7][5] = ChessSquare::Empty;
           board[7][4] = ChessSquare::BlackKing;
         },
       }
@@ -581,23 +645,31 @@ pub fn get_check(board: &[[ChessSquare; 8]; 8], player: &ChessColor) -> Option<C
   return None
 }
 
-pub fn get_valid_moves(last_move: &Option<ChessMove>, player: &ChessColor, board: &[[ChessSquare; 8]; 8], available_castling: &HashSet<Castling>) -> Vec<ChessMove> {
+pub fn get_valid_moves(en_passant: &Option<(usize, usize)>, player: &ChessColor, board: &[[ChessSquare; 8]; 8], available_castling: &HashSet<Castling>) -> Vec<ChessMove> {
   let mut testing_board = copy_board(board);
-  return get_potential_moves(last_move, player, board, available_castling).into_iter().filter(|m| {
+  return get_potential_moves(en_passant, player, board, available_castling).into_iter().filter(|m| {
     if board[m.y][m.x] == m.piece && testing_board[m.y][m.x] != m.piece {
       let fen1 = ChessBoard{
         last_move: None,
         squares: copy_board(board),
         current_player: player.clone(),
         available_castling: HashSet::new(),
-        move_number: 0
+        move_number: 0,
+        en_passant: None,
+        halfmove_clock: 0,
+        fullmove_number: 1,
+        hash: 0,
       }.get_forsyth_edwards_notation();
       let fen2 = ChessBoard{
         last_move: None,
         squares: copy_board(&testing_board),
         current_player: player.clone(),
         available_castling: HashSet::new(),
-        move_number: 0
+        move_number: 0,
+        en_passant: None,
+        halfmove_clock: 0,
+        fullmove_number: 1,
+        hash: 0,
       }.get_forsyth_edwards_notation();
       print!("Some jank shit occurred {} {}!", fen1, fen2);
     }
@@ -1,38 +1,536 @@
-use crate::{chess_board::ChessBoard, chess_color::ChessColor, chess_square::ChessSquare};
-
-fn get_piece_value(p: &ChessSquare) -> f32 {
-  match p {
-    ChessSquare::Empty => 0.0,
-  
-    ChessSquare::WhitePawn => 1.0,
-    ChessSquare::WhiteRook=> 5.0,
-    ChessSquare::WhiteKnight=> 3.0,
-    ChessSquare::WhiteBishop=> 3.0,
-    ChessSquare::WhiteQueen=> 9.0,
-    ChessSquare::WhiteKing=> 1000.0,
-  
-    ChessSquare::BlackPawn=> -1.0,
-    ChessSquare::BlackRook=> -5.0,
-    ChessSquare::BlackKnight=> -3.0,
-    ChessSquare::BlackBishop=> -3.0,
-    ChessSquare::BlackQueen=> -9.0,
-    ChessSquare::BlackKing=> -1000.0,
-  }
-}
-
-pub fn score_game_state(board: &ChessBoard) -> f32 {
-  let mut score = 0.0;
-  for y in 0..8 {
-    for x in 0..8 {
-      score += get_piece_value(&board.squares[y][x]);
-    }
-  }
-  let valid_moves = board.get_valid_moves();
-  for m in valid_moves.iter() {
-    score += match m.piece.get_color() {
-      ChessColor::White => 0.05,
-      ChessColor::Black => -0.05,
-    };
+use std::{ops::{AddAssign, Mul, SubAssign}, sync::Arc};
+
+use crate::{bitboard::{BISHOP, FILE_A, KING, KING_ATTACKS, KNIGHT, KNIGHT_ATTACKS, PAWN, PAWN_ATTACKS, Position, QUEEN, ROOK, WHITE, from_chess_board, get_bishop_attacks, get_pawn_attacks, get_rook_attacks}, chess_board::ChessBoard, eval_params::EvalParams, pawn_table::{DEFAULT_PAWN_TABLE_MEGABYTES, PawnTable}};
+
+// how much each piece kind counts towards the game phase; the starting position adds up to MAX_PHASE
+const PHASE_WEIGHTS: [i32; 6] = [0, 2, 1, 1, 4, 0];
+pub const MAX_PHASE: i32 = 24;
+
+// A score with a middlegame and an endgame value, in pawns from white's side.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct TaperedScore {
+  pub middlegame: f32,
+  pub endgame: f32,
+}
+
+impl TaperedScore {
+  // the blend of the two values for a phase between 0, a bare endgame, and MAX_PHASE
+  pub fn get_blended(&self, phase: i32) -> f32 {
+    return (self.middlegame * phase as f32 + self.endgame * (MAX_PHASE - phase) as f32) / MAX_PHASE as f32;
+  }
+}
+
+impl AddAssign for TaperedScore {
+  fn add_assign(&mut self, other: TaperedScore) {
+    self.middlegame += other.middlegame;
+    self.endgame += other.endgame;
+  }
+}
+
+impl Mul<f32> for TaperedScore {
+  type Output = TaperedScore;
+
+  fn mul(self, factor: f32) -> TaperedScore {
+    return create_tapered_score(self.middlegame * factor, self.endgame * factor);
+  }
+}
+
+impl SubAssign for TaperedScore {
+  fn sub_assign(&mut self, other: TaperedScore) {
+    self.middlegame -= other.middlegame;
+    self.endgame -= other.endgame;
+  }
+}
+
+pub fn create_tapered_score(middlegame: f32, endgame: f32) -> TaperedScore {
+  return TaperedScore{ middlegame: middlegame, endgame: endgame };
+}
+
+// The terms of a position's score, each from white's side, kept apart so they can be looked at one by one.
+#[derive(Debug, Clone)]
+pub struct Evaluation {
+  pub material: TaperedScore,
+  pub piece_squares: TaperedScore,
+  // a little for each square the pieces of either side can go to safely from pawns
+  pub mobility: TaperedScore,
+  // doubled, isolated, backward and passed pawns
+  pub pawn_structure: TaperedScore,
+  // pawn shields and enemy pieces attacking the squares around the kings
+  pub king_safety: TaperedScore,
+  // the bishop pair, rooks on open and semi-open files, and outposts
+  pub pieces: TaperedScore,
+  // MAX_PHASE with all the pieces on the board, down to 0 with only kings and pawns
+  pub phase: i32,
+}
+
+impl Evaluation {
+  pub fn get_total(&self) -> TaperedScore {
+    let mut total = self.material;
+    total += self.piece_squares;
+    total += self.mobility;
+    total += self.pawn_structure;
+    total += self.king_safety;
+    total += self.pieces;
+    return total;
+  }
+
+  pub fn get_score(&self) -> f32 {
+    return self.get_total().get_blended(self.phase);
+  }
+
+  // each term as middlegame and endgame values, then the blended score
+  pub fn to_string(&self) -> String {
+    let mut lines = Vec::new();
+    let terms = [
+      ("material", &self.material),
+      ("piece squares", &self.piece_squares),
+      ("mobility", &self.mobility),
+      ("pawn structure", &self.pawn_structure),
+      ("king safety", &self.king_safety),
+      ("pieces", &self.pieces),
+    ];
+    for (name, term) in terms {
+      lines.push(format!("{:<14} {:>7.2} {:>7.2}", name, term.middlegame, term.endgame));
+    }
+    lines.push(format!("phase {} of {}, score {:.2}", self.phase, MAX_PHASE, self.get_score()));
+    return lines.join("\n");
+  }
+}
+
+// Scores positions for a search, caching the pawn structure terms. Each search thread has one of its own, and
+// the cache is only good for the parameters it was filled with.
+pub struct Evaluator {
+  pub pawn_table: PawnTable,
+  pub params: Arc<EvalParams>,
+}
+
+impl Evaluator {
+  pub fn evaluate(&mut self, position: &Position) -> Evaluation {
+    return get_evaluation(position, &self.params, Some(&mut self.pawn_table));
+  }
+
+  pub fn score_position(&mut self, position: &Position) -> f32 {
+    return self.evaluate(position).get_score();
+  }
+
+  // the cached pawn structure scores only hold for the parameters they were scored with, so they go too
+  pub fn set_params(&mut self, params: Arc<EvalParams>) {
+    self.params = params;
+    self.pawn_table.clear();
+  }
+}
+
+pub fn create_evaluator(params: Arc<EvalParams>) -> Evaluator {
+  return Evaluator{ pawn_table: PawnTable::new(DEFAULT_PAWN_TABLE_MEGABYTES), params: params };
+}
+
+// the evaluation of a single position, with nothing cached
+pub fn evaluate(position: &Position, params: &EvalParams) -> Evaluation {
+  return get_evaluation(position, params, None);
+}
+
+fn get_evaluation(position: &Position, params: &EvalParams, pawn_table: Option<&mut PawnTable>) -> Evaluation {
+  let mut evaluation = Evaluation{
+    material: TaperedScore::default(),
+    piece_squares: TaperedScore::default(),
+    mobility: TaperedScore::default(),
+    pawn_structure: TaperedScore::default(),
+    king_safety: TaperedScore::default(),
+    pieces: TaperedScore::default(),
+    phase: 0,
+  };
+  for kind in [PAWN, ROOK, KNIGHT, BISHOP, QUEEN, KING] {
+    let kind = kind as usize;
+    for color in 0..2 {
+      let mut bits = position.pieces[color * 6 + kind];
+      evaluation.phase += bits.count_ones() as i32 * PHASE_WEIGHTS[kind];
+      while bits != 0 {
+        let square = bits.trailing_zeros() as usize;
+        bits &= bits - 1;
+        let table_square = if color == WHITE { square ^ 56 } else { square };
+        let value = params.piece_values[kind];
+        let piece_square = params.piece_squares[kind][table_square];
+        if color == WHITE {
+          evaluation.material += value;
+          evaluation.piece_squares += piece_square;
+        } else {
+          evaluation.material -= value;
+          evaluation.piece_squares -= piece_square;
+        }
+      }
+    }
+  }
+  // promotions can take the phase past the start
+  evaluation.phase = evaluation.phase.min(MAX_PHASE);
+
+  evaluation.pawn_structure = match pawn_table {
+    Some(table) => match table.probe(position.pawn_hash) {
+      Some(score) => score,
+      None => {
+        let score = get_pawn_structure(position, params);
+        table.store(position.pawn_hash, score);
+        score
+      }
+    },
+    None => get_pawn_structure(position, params),
+  };
+  for color in 0..2 {
+    let (mobility, king_attacks) = get_piece_activity(position, color, params);
+    let mut king_safety = get_pawn_shield(position, color, params);
+    king_safety += king_attacks;
+    let pieces = get_piece_placement(position, color, params);
+    if color == WHITE {
+      evaluation.mobility += mobility;
+      evaluation.king_safety += king_safety;
+      evaluation.pieces += pieces;
+    } else {
+      evaluation.mobility -= mobility;
+      evaluation.king_safety -= king_safety;
+      evaluation.pieces -= pieces;
+    }
+  }
+  return evaluation;
+}
+
+fn get_file_mask(file: usize) -> u64 {
+  return FILE_A << file;
+}
+
+fn get_adjacent_files(file: usize) -> u64 {
+  let mut files = 0;
+  if file > 0 {
+    files |= get_file_mask(file - 1);
+  }
+  if file < 7 {
+    files |= get_file_mask(file + 1);
+  }
+  return files;
+}
+
+// every rank in front of the given one, as seen by color
+fn get_ranks_ahead(rank: usize, color: usize) -> u64 {
+  if color == WHITE {
+    return if rank >= 7 { 0 } else { !0 << ((rank + 1) * 8) };
+  }
+  return (1 << (rank * 8)) - 1;
+}
+
+// the rank counted from color's own side, 0 being its back rank
+fn get_relative_rank(square: usize, color: usize) -> usize {
+  return if color == WHITE { square / 8 } else { 7 - square / 8 };
+}
+
+// Both sides' pawn terms from white's side. Only the pawns go into it, so the pawn hash is enough to cache it.
+fn get_pawn_structure(position: &Position, params: &EvalParams) -> TaperedScore {
+  let mut total = TaperedScore::default();
+  for color in 0..2 {
+    let pawns = position.pieces[color * 6 + PAWN as usize];
+    let enemy_pawns = position.pieces[(1 - color) * 6 + PAWN as usize];
+    let enemy_attacks = get_pawn_attacks(enemy_pawns, 1 - color);
+    let mut score = TaperedScore::default();
+    for file in 0..8 {
+      let count = (pawns & get_file_mask(file)).count_ones();
+      if count > 1 {
+        score += params.doubled_pawn * (count - 1) as f32;
+      }
+    }
+    let mut bits = pawns;
+    while bits != 0 {
+      let square = bits.trailing_zeros() as usize;
+      bits &= bits - 1;
+      let file = square % 8;
+      let rank = square / 8;
+      let adjacent_files = get_adjacent_files(file);
+      // a pawn on its last rank, which only a position set up by hand can have, has nowhere to advance to
+      let stop = match get_relative_rank(square, color) {
+        7 => 0,
+        _ => if color == WHITE { 1 << (square + 8) } else { 1 << (square - 8) },
+      };
+      if pawns & adjacent_files == 0 {
+        score += params.isolated_pawn;
+      } else if pawns & adjacent_files & !get_ranks_ahead(rank, color) == 0 && enemy_attacks & stop != 0 {
+        // no pawn beside or behind it can come up to defend it, and it can't advance without being taken
+        score += params.backward_pawn;
+      }
+      if enemy_pawns & (adjacent_files | get_file_mask(file)) & get_ranks_ahead(rank, color) == 0 {
+        score += params.passed_pawn[get_relative_rank(square, color)];
+      }
+    }
+    if color == WHITE {
+      total += score;
+    } else {
+      total -= score;
+    }
+  }
+  return total;
+}
+
+// the pawns in front of color's king, as a bonus for color
+fn get_pawn_shield(position: &Position, color: usize, params: &EvalParams) -> TaperedScore {
+  let mut score = TaperedScore::default();
+  let king = position.pieces[color * 6 + KING as usize];
+  if king == 0 {
+    return score;
+  }
+  let square = king.trailing_zeros() as usize;
+  let pawns = position.pieces[color * 6 + PAWN as usize];
+  let shield_files = get_adjacent_files(square % 8) | get_file_mask(square % 8);
+  let relative_rank = get_relative_rank(square, color);
+  for (distance, bonus) in [(1, params.pawn_shield_close), (2, params.pawn_shield_far)] {
+    if relative_rank + distance > 7 {
+      break;
+    }
+    let rank = if color == WHITE { square / 8 + distance } else { square / 8 - distance };
+    score += bonus * (pawns & shield_files & (0xFF << (rank * 8))).count_ones() as f32;
   }
   return score;
-}
\ No newline at end of file
+}
+
+// The mobility of color's pieces and their attacks on the squares around the enemy king, both as bonuses for
+// color. Moves are counted from the squares each piece attacks, without checking they are legal, so it costs
+// a few lookups per piece and comes out the same whichever side is to move.
+fn get_piece_activity(position: &Position, color: usize, params: &EvalParams) -> (TaperedScore, TaperedScore) {
+  let mut mobility = TaperedScore::default();
+  let mut king_attacks = TaperedScore::default();
+  let enemy = 1 - color;
+  let occupied = position.colors[0] | position.colors[1];
+  let available = !position.colors[color] & !get_pawn_attacks(position.pieces[enemy * 6 + PAWN as usize], enemy);
+  let enemy_king = position.pieces[enemy * 6 + KING as usize];
+  let zone = if enemy_king == 0 { 0 } else { KING_ATTACKS[enemy_king.trailing_zeros() as usize] | enemy_king };
+  for kind in [ROOK, KNIGHT, BISHOP, QUEEN] {
+    let kind_index = kind as usize;
+    let mut bits = position.pieces[color * 6 + kind_index];
+    while bits != 0 {
+      let square = bits.trailing_zeros() as usize;
+      bits &= bits - 1;
+      let attacks = get_piece_attacks(kind, square, occupied);
+      let moves = (attacks & available).count_ones() as f32;
+      mobility += params.mobility[kind_index] * moves;
+      king_attacks += params.king_attack[kind_index] * (attacks & zone).count_ones() as f32;
+    }
+  }
+  return (mobility, king_attacks);
+}
+
+fn get_piece_attacks(kind: u8, square: usize, occupied: u64) -> u64 {
+  return match kind {
+    ROOK => get_rook_attacks(square, occupied),
+    KNIGHT => KNIGHT_ATTACKS[square],
+    BISHOP => get_bishop_attacks(square, occupied),
+    QUEEN => get_rook_attacks(square, occupied) | get_bishop_attacks(square, occupied),
+    _ => 0,
+  };
+}
+
+// the bishop pair, rooks on open and semi-open files and outposts, as a bonus for color
+fn get_piece_placement(position: &Position, color: usize, params: &EvalParams) -> TaperedScore {
+  let mut score = TaperedScore::default();
+  if position.pieces[color * 6 + BISHOP as usize].count_ones() >= 2 {
+    score += params.bishop_pair;
+  }
+  let pawns = position.pieces[color * 6 + PAWN as usize];
+  let enemy_pawns = position.pieces[(1 - color) * 6 + PAWN as usize];
+  let mut rooks = position.pieces[color * 6 + ROOK as usize];
+  while rooks != 0 {
+    let square = rooks.trailing_zeros() as usize;
+    rooks &= rooks - 1;
+    let file = get_file_mask(square % 8);
+    if (pawns | enemy_pawns) & file == 0 {
+      score += params.rook_open_file;
+    } else if pawns & file == 0 {
+      score += params.rook_semi_open_file;
+    }
+  }
+  for (kind, bonus) in [(KNIGHT, params.knight_outpost), (BISHOP, params.bishop_outpost)] {
+    let mut bits = position.pieces[color * 6 + kind as usize];
+    while bits != 0 {
+      let square = bits.trailing_zeros() as usize;
+      bits &= bits - 1;
+      let relative_rank = get_relative_rank(square, color);
+      // a pawn of color defends the square if a pawn of the other color on it would attack the pawn
+      let defended = PAWN_ATTACKS[1 - color][square] & pawns != 0;
+      let attackable = enemy_pawns & get_adjacent_files(square % 8) & get_ranks_ahead(square / 8, color) != 0;
+      if (3..=5).contains(&relative_rank) && defended && !attackable {
+        score += bonus;
+      }
+    }
+  }
+  return score;
+}
+
+pub fn score_game_state(board: &ChessBoard, params: &EvalParams) -> f32 {
+  return score_position(&from_chess_board(board), params);
+}
+
+pub fn score_position(position: &Position, params: &EvalParams) -> f32 {
+  return evaluate(position, params).get_score();
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::bitboard::{BLACK, PAWN, Position, WHITE, from_chess_board};
+  use crate::chess_board::{create_new_board, from_forsyth_edwards_notation};
+  use crate::eval_params::{EvalParams, create_default_params};
+  use super::{MAX_PHASE, TaperedScore, create_evaluator, create_tapered_score, evaluate, get_pawn_shield, get_pawn_structure, get_piece_activity, get_piece_placement, score_position};
+
+  const KIWIPETE: &str = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+
+  fn get_position(fen: &str) -> Position {
+    return from_chess_board(&from_forsyth_edwards_notation(fen).unwrap());
+  }
+
+  fn score(fen: &str) -> f32 {
+    return score_position(&get_position(fen), &EvalParams::default());
+  }
+
+  fn assert_term(term: TaperedScore, middlegame: f32, endgame: f32, fen: &str) {
+    assert!((term.middlegame - middlegame).abs() < 1e-5 && (term.endgame - endgame).abs() < 1e-5, "{:?} for {}", term, fen);
+  }
+
+  // the same position with the colors swapped, so white's pieces stand where black's did, and the other side to move
+  fn mirror(fen: &str) -> String {
+    let fields: Vec<&str> = fen.split(' ').collect();
+    let swap_case = |s: &str| s.chars().map(|c| if c.is_ascii_uppercase() { c.to_ascii_lowercase() } else { c.to_ascii_uppercase() }).collect::<String>();
+    let placement = fields[0].split('/').rev().map(swap_case).collect::<Vec<String>>().join("/");
+    let side = if fields[1] == "w" { "b" } else { "w" };
+    let mut castling: Vec<char> = swap_case(fields[2]).chars().collect();
+    castling.sort_by_key(|c| c.is_ascii_lowercase());
+    let en_passant = fields[3].replace('3', "x").replace('6', "3").replace('x', "6");
+    return format!("{} {} {} {} {} {}", placement, side, castling.into_iter().collect::<String>(), en_passant, fields[4], fields[5]);
+  }
+
+  #[test]
+  fn phase() {
+    let params = EvalParams::default();
+    assert_eq!(evaluate(&from_chess_board(&create_new_board()), &params).phase, MAX_PHASE);
+    assert_eq!(evaluate(&get_position("4k3/8/8/8/8/8/8/4K3 w - - 0 1"), &params).phase, 0);
+    // kings and pawns count for nothing, a rook for two
+    assert_eq!(evaluate(&get_position("4k3/pppp4/8/8/8/8/PPPP4/R3K3 w - - 0 1"), &params).phase, 2);
+  }
+
+  #[test]
+  fn tapers_between_middlegame_and_endgame() {
+    let term = create_tapered_score(1.0, 3.0);
+    assert_eq!(term.get_blended(MAX_PHASE), 1.0);
+    assert_eq!(term.get_blended(0), 3.0);
+    assert_eq!(term.get_blended(MAX_PHASE / 2), 2.0);
+    assert_eq!(term.get_blended(MAX_PHASE / 4), 2.5);
+    let evaluation = evaluate(&get_position(KIWIPETE), &EvalParams::default());
+    assert_eq!(evaluation.get_score(), evaluation.get_total().get_blended(evaluation.phase));
+  }
+
+  #[test]
+  fn mirrored_positions_score_negated() {
+    assert_eq!(mirror(KIWIPETE), "r3k2r/pppbbppp/2n2q1P/1P2p3/3pn3/BN2PNP1/P1PPQPB1/R3K2R b KQkq - 0 1");
+    for fen in [
+      KIWIPETE,
+      "rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR w KQkq e6 0 2",
+      "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+      "r1b1kb1r/pppp1ppp/5q2/4n3/3KP3/2N3PN/PPP4P/R1BQ1B1R b kq - 0 1",
+    ] {
+      // the terms are added up in another order, so only equal to rounding
+      assert!((score(&mirror(fen)) + score(fen)).abs() < 1e-4, "{}", fen);
+    }
+  }
+
+  #[test]
+  fn pawns_on_the_last_rank() {
+    // the fen parser won't allow these, so they are put on the board by hand, along with the same pawns with
+    // the colors swapped and the board flipped
+    let kings = from_chess_board(&from_forsyth_edwards_notation("4k3/8/8/8/8/8/8/4K3 w - - 0 1").unwrap());
+    let mut position = kings;
+    position.pieces[WHITE * 6 + PAWN as usize] = 1 << 56 | 1 << 9;
+    position.pieces[BLACK * 6 + PAWN as usize] = 1 << 7;
+    let mut mirrored = kings;
+    mirrored.pieces[BLACK * 6 + PAWN as usize] = 1 << 0 | 1 << 49;
+    mirrored.pieces[WHITE * 6 + PAWN as usize] = 1 << 63;
+    let score = get_pawn_structure(&position, &EvalParams::default());
+    let mirrored_score = get_pawn_structure(&mirrored, &EvalParams::default());
+    assert_eq!(score.middlegame, -mirrored_score.middlegame);
+    assert_eq!(score.endgame, -mirrored_score.endgame);
+  }
+
+  #[test]
+  fn pawn_structure() {
+    for (fen, middlegame, endgame) in [
+      // doubled on the c-file
+      ("4k3/2pp4/8/8/8/2P5/2PP4/4K3 w - - 0 1", -0.10, -0.25),
+      // isolated on the a- and c-files
+      ("4k3/1pp5/8/8/8/8/P1P5/4K3 w - - 0 1", -0.30, -0.40),
+      // e3 can't be defended by a pawn and d5 covers e4
+      ("4k3/8/4pp2/3p4/3P4/4P3/8/4K3 w - - 0 1", -0.10, -0.15),
+      // isolated but passed, for more the further it has come
+      ("4k3/8/8/4P3/8/8/8/4K3 w - - 0 1", 0.15, 0.40),
+      ("4k3/8/4P3/8/8/8/8/4K3 w - - 0 1", 0.35, 0.80),
+      ("4k3/8/8/8/4p3/8/8/4K3 w - - 0 1", -0.15, -0.40),
+    ] {
+      assert_term(get_pawn_structure(&get_position(fen), &EvalParams::default()), middlegame, endgame, fen);
+    }
+  }
+
+  #[test]
+  fn pawn_shield() {
+    for (fen, middlegame) in [
+      ("6k1/8/8/8/8/8/5PPP/6K1 w - - 0 1", 0.36),
+      ("6k1/8/8/8/8/5P2/6PP/6K1 w - - 0 1", 0.30),
+      ("6k1/8/8/8/8/8/PPP5/6K1 w - - 0 1", 0.0),
+    ] {
+      assert_term(get_pawn_shield(&get_position(fen), WHITE, &EvalParams::default()), middlegame, 0.0, fen);
+    }
+  }
+
+  #[test]
+  fn piece_placement() {
+    for (fen, middlegame, endgame) in [
+      ("4k3/8/8/8/8/8/8/2B1KB2 w - - 0 1", 0.30, 0.50),
+      ("4k3/8/8/8/8/8/8/2B1K3 w - - 0 1", 0.0, 0.0),
+      // rooks on an open, a semi-open and a closed file
+      ("4k3/8/8/8/8/8/8/R3K3 w - - 0 1", 0.25, 0.10),
+      ("4k3/p7/8/8/8/8/8/R3K3 w - - 0 1", 0.10, 0.05),
+      ("4k3/8/8/8/8/8/P7/R3K3 w - - 0 1", 0.0, 0.0),
+      // outposts defended by the d-pawn, and the knight again where the f-pawn can drive it off
+      ("4k3/8/8/4N3/3P4/8/8/4K3 w - - 0 1", 0.30, 0.20),
+      ("4k3/8/8/4B3/3P4/8/8/4K3 w - - 0 1", 0.15, 0.10),
+      ("4k3/5p2/8/4N3/3P4/8/8/4K3 w - - 0 1", 0.0, 0.0),
+      ("4k3/8/8/4N3/8/8/8/4K3 w - - 0 1", 0.0, 0.0),
+    ] {
+      assert_term(get_piece_placement(&get_position(fen), WHITE, &EvalParams::default()), middlegame, endgame, fen);
+    }
+  }
+
+  #[test]
+  fn cached_pawn_structure() {
+    let position = get_position(KIWIPETE);
+    let params = create_default_params();
+    let mut evaluator = create_evaluator(params.clone());
+    evaluator.evaluate(&position);
+    let cached = evaluator.evaluate(&position);
+    assert_eq!(evaluator.pawn_table.hits, 1);
+    assert_eq!(cached.pawn_structure, evaluate(&position, &params).pawn_structure);
+    assert_eq!(cached.get_score(), evaluate(&position, &params).get_score());
+  }
+
+  #[test]
+  fn mobility_is_the_same_for_either_side_to_move() {
+    let params = EvalParams::default();
+    let white_to_move = evaluate(&get_position(KIWIPETE), &params).mobility;
+    let black_to_move = evaluate(&get_position("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R b KQkq - 0 1"), &params).mobility;
+    assert_eq!(white_to_move, black_to_move);
+    assert_ne!(white_to_move, TaperedScore::default());
+  }
+
+  #[test]
+  fn mobility_leaves_out_squares_covered_by_pawns() {
+    for (fen, mobility) in [
+      // the knight can go to b3 and c2
+      ("4k3/8/8/8/8/8/8/N3K3 w - - 0 1", 0.08),
+      // c4 covers b3 and d3 covers c2
+      ("4k3/8/8/8/2p5/3p4/8/N3K3 w - - 0 1", 0.0),
+      // its own pawns stand on both
+      ("4k3/8/8/8/8/1P6/2P5/N3K3 w - - 0 1", 0.0),
+    ] {
+      let (score, _) = get_piece_activity(&get_position(fen), WHITE, &EvalParams::default());
+      assert_term(score, mobility, mobility, fen);
+    }
+  }
+}
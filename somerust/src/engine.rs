@@ -0,0 +1,286 @@
+use std::{sync::{Arc, atomic::Ordering, mpsc::{self, Receiver, RecvTimeoutError, Sender}}, thread::{self, JoinHandle}, time::Instant};
+
+use crate::{chess_game::ChessGame, negamax, search::{SearchInfo, SearchLimits, SearchOptions, SearchResult, SharedSearch}, transposition_table::TranspositionTable};
+
+// What the engine can be told to do, in the spirit of the UCI commands of the same names.
+pub enum EngineCommand {
+  // the game later searches start from; any search in progress is abandoned without a result
+  NewPosition(Box<ChessGame>),
+  // Search the current position. A ponder search is on the opponent's time: it ignores the time limits and
+  // holds back its result until ponderhit or stop, after which it plays by the limits as given.
+  Go { limits: SearchLimits, ponder: bool },
+  // finish the search in progress now and report its best move
+  Stop,
+  // the opponent played the move being pondered on, so the ponder search becomes a normal one
+  PonderHit,
+  // options for the searches started after it, including the evaluation parameters
+  SetOptions(SearchOptions),
+}
+
+#[derive(Debug, Clone)]
+pub enum EngineEvent {
+  Info(SearchInfo),
+  // The result of a search that wasn't abandoned, with the key of the position searched. best_move is none if
+  // the position had no moves, and the second move of the pv, if there is one, is the reply worth pondering on.
+  BestMove(u64, SearchResult),
+}
+
+// A search engine on its own thread, so whoever drives it never waits on a search. Commands are queued and
+// events are picked up with try_recv whenever it suits the caller. The table is kept between searches.
+pub struct EngineHandle {
+  commands: Sender<WorkerMessage>,
+  events: Receiver<EngineEvent>,
+  pub table: Arc<TranspositionTable>,
+  worker: Option<JoinHandle<()>>,
+}
+
+impl EngineHandle {
+  pub fn send(&self, command: EngineCommand) {
+    // the worker only goes away when the handle is dropped
+    self.commands.send(WorkerMessage::Command(command)).unwrap();
+  }
+
+  pub fn try_recv(&self) -> Option<EngineEvent> {
+    return self.events.try_recv().ok();
+  }
+}
+
+impl Drop for EngineHandle {
+  fn drop(&mut self) {
+    let _ = self.commands.send(WorkerMessage::Quit);
+    if let Some(worker) = self.worker.take() {
+      let _ = worker.join();
+    }
+  }
+}
+
+pub fn create_engine_handle(table_megabytes: usize, threads: usize, options: SearchOptions) -> EngineHandle {
+  let (command_sender, command_receiver) = mpsc::channel();
+  let (event_sender, event_receiver) = mpsc::channel();
+  let table = Arc::new(TranspositionTable::new(table_megabytes));
+  let worker = Worker{
+    sender: command_sender.clone(),
+    events: event_sender,
+    table: table.clone(),
+    threads: threads,
+    options: options,
+  };
+  let worker = thread::spawn(move || {
+    run_worker(command_receiver, worker);
+  });
+  return EngineHandle{
+    commands: command_sender,
+    events: event_receiver,
+    table: table,
+    worker: Some(worker),
+  };
+}
+
+enum WorkerMessage {
+  Command(EngineCommand),
+  // sent by a search thread when it finishes, with the id of its search
+  SearchDone(u64, Box<SearchResult>),
+  Quit,
+}
+
+// what the worker keeps for the whole life of the engine, and hands on to each search it starts
+struct Worker {
+  // for search threads to report back to the worker on
+  sender: Sender<WorkerMessage>,
+  events: Sender<EngineEvent>,
+  table: Arc<TranspositionTable>,
+  threads: usize,
+  options: SearchOptions,
+}
+
+// the search the worker has running, or had running and is holding the result of
+struct ActiveSearch {
+  id: u64,
+  // the key of the position searched, reported along with the result
+  key: u64,
+  shared: Arc<SharedSearch>,
+  thread: JoinHandle<()>,
+  limits: SearchLimits,
+  pondering: bool,
+  // when to stop a search that was pondering, counted from ponderhit
+  stop_instant: Option<Instant>,
+  // a result that came in while still pondering
+  held_result: Option<SearchResult>,
+}
+
+impl ActiveSearch {
+  fn stop(&self) {
+    self.shared.stop.store(true, Ordering::Relaxed);
+  }
+}
+
+// The worker handles commands in order and leaves searching to a thread of its own per search, so it can stop
+// a search or time one out after ponderhit while the search runs.
+fn run_worker(commands: Receiver<WorkerMessage>, mut worker: Worker) {
+  let mut game: Option<ChessGame> = None;
+  let mut active: Option<ActiveSearch> = None;
+  let mut next_id = 0;
+  loop {
+    let message = match active.as_ref().and_then(|a| a.stop_instant) {
+      Some(stop_instant) => match commands.recv_timeout(stop_instant.saturating_duration_since(Instant::now())) {
+        Ok(message) => message,
+        Err(RecvTimeoutError::Timeout) => {
+          if let Some(a) = active.as_mut() {
+            a.stop();
+            a.stop_instant = None;
+          }
+          continue;
+        }
+        Err(RecvTimeoutError::Disconnected) => WorkerMessage::Quit,
+      },
+      None => commands.recv().unwrap_or(WorkerMessage::Quit),
+    };
+    match message {
+      WorkerMessage::Command(EngineCommand::NewPosition(new_game)) => {
+        abandon_search(active.take());
+        game = Some(*new_game);
+      }
+      WorkerMessage::Command(EngineCommand::Go{ limits, ponder }) => {
+        abandon_search(active.take());
+        if let Some(g) = game.as_ref() {
+          next_id += 1;
+          active = Some(start_search(&worker, next_id, g.clone(), limits, ponder));
+        }
+      }
+      WorkerMessage::Command(EngineCommand::Stop) => {
+        if let Some(a) = active.as_mut() {
+          a.pondering = false;
+          a.stop();
+          if let Some(result) = a.held_result.take() {
+            let _ = worker.events.send(EngineEvent::BestMove(a.key, result));
+          }
+        }
+      }
+      WorkerMessage::Command(EngineCommand::PonderHit) => {
+        if let Some(a) = active.as_mut() {
+          if a.pondering {
+            a.pondering = false;
+            match a.held_result.take() {
+              Some(result) => {
+                let _ = worker.events.send(EngineEvent::BestMove(a.key, result));
+              }
+              // with no time limit the ponder search is given up to the depth or nodes it was limited to
+              None => a.stop_instant = a.limits.get_stop_instant(Instant::now()),
+            }
+          }
+        }
+      }
+      WorkerMessage::Command(EngineCommand::SetOptions(new_options)) => {
+        worker.options = new_options;
+      }
+      WorkerMessage::SearchDone(id, result) => {
+        if let Some(a) = active.as_mut() {
+          if a.id == id {
+            if a.pondering {
+              a.held_result = Some(*result);
+            } else {
+              let _ = worker.events.send(EngineEvent::BestMove(a.key, *result));
+            }
+            a.stop_instant = None;
+          }
+        }
+      }
+      WorkerMessage::Quit => {
+        abandon_search(active.take());
+        return;
+      }
+    }
+  }
+}
+
+fn start_search(worker: &Worker, id: u64, game: ChessGame, limits: SearchLimits, ponder: bool) -> ActiveSearch {
+  let shared = Arc::new(SharedSearch::default());
+  let key = game.board.hash;
+  // a ponder search runs until told otherwise, apart from depth and node limits
+  let search_limits = if ponder { SearchLimits{ movetime: None, deadline: None, ..limits.clone() } } else { limits.clone() };
+  let search_shared = shared.clone();
+  let search_table = worker.table.clone();
+  let search_options = worker.options.clone();
+  let threads = worker.threads;
+  let sender = worker.sender.clone();
+  let events = worker.events.clone();
+  let thread = thread::spawn(move || {
+    search_table.reset_stats();
+    let result = negamax::think_parallel_shared(&game, &search_limits, &search_options, &search_table, threads, &search_shared, &mut |info| {
+      let _ = events.send(EngineEvent::Info(info.clone()));
+    });
+    let _ = sender.send(WorkerMessage::SearchDone(id, Box::new(result)));
+  });
+  return ActiveSearch{
+    id: id,
+    key: key,
+    shared: shared,
+    thread: thread,
+    limits: limits,
+    pondering: ponder,
+    stop_instant: None,
+    held_result: None,
+  };
+}
+
+// stops a search whose result is no longer wanted; a SearchDone it already sent is ignored for having an old id
+fn abandon_search(active: Option<ActiveSearch>) {
+  if let Some(a) = active {
+    a.stop();
+    let _ = a.thread.join();
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::{thread, time::{Duration, Instant}};
+  use crate::chess_board::create_new_board;
+  use crate::chess_game::create_game;
+  use crate::search::{SearchLimits, SearchOptions, SearchResult};
+  use super::{EngineCommand, EngineEvent, EngineHandle, create_engine_handle};
+
+  // the next result within the time given, passing over info events
+  fn wait_for_best_move(engine: &EngineHandle, timeout: Duration) -> Option<(u64, SearchResult)> {
+    let give_up = Instant::now() + timeout;
+    while Instant::now() < give_up {
+      match engine.try_recv() {
+        Some(EngineEvent::BestMove(key, result)) => return Some((key, result)),
+        Some(EngineEvent::Info(_)) => {}
+        None => thread::sleep(Duration::from_millis(5)),
+      }
+    }
+    return None;
+  }
+
+  fn start_pondering(engine: &EngineHandle) {
+    engine.send(EngineCommand::NewPosition(Box::new(create_game(create_new_board()))));
+    engine.send(EngineCommand::Go{ limits: SearchLimits::default(), ponder: true });
+    thread::sleep(Duration::from_millis(50));
+  }
+
+  #[test]
+  fn ponder_ends_on_stop() {
+    let engine = create_engine_handle(1, 1, SearchOptions::default());
+    start_pondering(&engine);
+    assert!(wait_for_best_move(&engine, Duration::from_millis(50)).is_none());
+    engine.send(EngineCommand::Stop);
+    let (key, result) = wait_for_best_move(&engine, Duration::from_secs(5)).expect("no result after stop");
+    assert_eq!(key, create_new_board().hash);
+    assert!(result.best_move.is_some());
+  }
+
+  #[test]
+  fn ponder_ends_on_new_position() {
+    let engine = create_engine_handle(1, 1, SearchOptions::default());
+    start_pondering(&engine);
+    let mut game = create_game(create_new_board());
+    let first_move = game.board.get_valid_moves()[0].clone();
+    game.do_move(&first_move);
+    let key = game.board.hash;
+    engine.send(EngineCommand::NewPosition(Box::new(game)));
+    // the worker only gets to the new search once the ponder search has ended, which reports nothing
+    engine.send(EngineCommand::Go{ limits: SearchLimits{ max_depth: Some(1), ..Default::default() }, ponder: false });
+    let (result_key, _) = wait_for_best_move(&engine, Duration::from_secs(5)).expect("ponder search still running");
+    assert_eq!(result_key, key);
+  }
+}
@@ -0,0 +1,446 @@
+use std::{fmt, fs, io, sync::Arc};
+
+use crate::chess_scoring::{TaperedScore, create_tapered_score};
+
+// names of the piece kinds in the order bitboard indexes them, for the keys of the parameter file
+const KIND_NAMES: [&str; 6] = ["pawn", "rook", "knight", "bishop", "queen", "king"];
+// the kinds that have mobility and king attack weights
+const PIECE_KINDS: [usize; 4] = [1, 2, 3, 4];
+
+// The default piece values and piece-square tables, in centipawns.
+const DEFAULT_MIDDLEGAME_VALUES: [i32; 6] = [82, 477, 337, 365, 1025, 0];
+const DEFAULT_ENDGAME_VALUES: [i32; 6] = [94, 512, 281, 297, 936, 0];
+
+const DEFAULT_MIDDLEGAME_TABLES: [[i32; 64]; 6] = [
+  [
+      0,    0,    0,    0,    0,    0,    0,    0,
+     98,  134,   61,   95,   68,  126,   34,  -11,
+     -6,    7,   26,   31,   65,   56,   25,  -20,
+    -14,   13,    6,   21,   23,   12,   17,  -23,
+    -27,   -2,   -5,   12,   17,    6,   10,  -25,
+    -26,   -4,   -4,  -10,    3,    3,   33,  -12,
+    -35,   -1,  -20,  -23,  -15,   24,   38,  -22,
+      0,    0,    0,    0,    0,    0,    0,    0,
+  ],
+  [
+     32,   42,   32,   51,   63,    9,   31,   43,
+     27,   32,   58,   62,   80,   67,   26,   44,
+     -5,   19,   26,   36,   17,   45,   61,   16,
+    -24,  -11,    7,   26,   24,   35,   -8,  -20,
+    -36,  -26,  -12,   -1,    9,   -7,    6,  -23,
+    -45,  -25,  -16,  -17,    3,    0,   -5,  -33,
+    -44,  -16,  -20,   -9,   -1,   11,   -6,  -71,
+    -19,  -13,    1,   17,   16,    7,  -37,  -26,
+  ],
+  [
+   -167,  -89,  -34,  -49,   61,  -97,  -15, -107,
+    -73,  -41,   72,   36,   23,   62,    7,  -17,
+    -47,   60,   37,   65,   84,  129,   73,   44,
+     -9,   17,   19,   53,   37,   69,   18,   22,
+    -13,    4,   16,   13,   28,   19,   21,   -8,
+    -23,   -9,   12,   10,   19,   17,   25,  -16,
+    -29,  -53,  -12,   -3,   -1,   18,  -14,  -19,
+   -105,  -21,  -58,  -33,  -17,  -28,  -19,  -23,
+  ],
+  [
+    -29,    4,  -82,  -37,  -25,  -42,    7,   -8,
+    -26,   16,  -18,  -13,   30,   59,   18,  -47,
+    -16,   37,   43,   40,   35,   50,   37,   -2,
+     -4,    5,   19,   50,   37,   37,    7,   -2,
+     -6,   13,   13,   26,   34,   12,   10,    4,
+      0,   15,   15,   15,   14,   27,   18,   10,
+      4,   15,   16,    0,    7,   21,   33,    1,
+    -33,   -3,  -14,  -21,  -13,  -12,  -39,  -21,
+  ],
+  [
+    -28,    0,   29,   12,   59,   44,   43,   45,
+    -24,  -39,   -5,    1,  -16,   57,   28,   54,
+    -13,  -17,    7,    8,   29,   56,   47,   57,
+    -27,  -27,  -16,  -16,   -1,   17,   -2,    1,
+     -9,  -26,   -9,  -10,   -2,   -4,    3,   -3,
+    -14,    2,  -11,   -2,   -5,    2,   14,    5,
+    -35,   -8,   11,    2,    8,   15,   -3,    1,
+     -1,  -18,   -9,   10,  -15,  -25,  -31,  -50,
+  ],
+  [
+    -65,   23,   16,  -15,  -56,  -34,    2,   13,
+     29,   -1,  -20,   -7,   -8,   -4,  -38,  -29,
+     -9,   24,    2,  -16,  -20,    6,   22,  -22,
+    -17,  -20,  -12,  -27,  -30,  -25,  -14,  -36,
+    -49,   -1,  -27,  -39,  -46,  -44,  -33,  -51,
+    -14,  -14,  -22,  -46,  -44,  -30,  -15,  -27,
+      1,    7,   -8,  -64,  -43,  -16,    9,    8,
+    -15,   36,   12,  -54,    8,  -28,   24,   14,
+  ],
+];
+
+const DEFAULT_ENDGAME_TABLES: [[i32; 64]; 6] = [
+  [
+      0,    0,    0,    0,    0,    0,    0,    0,
+    178,  173,  158,  134,  147,  132,  165,  187,
+     94,  100,   85,   67,   56,   53,   82,   84,
+     32,   24,   13,    5,   -2,    4,   17,   17,
+     13,    9,   -3,   -7,   -7,   -8,    3,   -1,
+      4,    7,   -6,    1,    0,   -5,   -1,   -8,
+     13,    8,    8,   10,   13,    0,    2,   -7,
+      0,    0,    0,    0,    0,    0,    0,    0,
+  ],
+  [
+     13,   10,   18,   15,   12,   12,    8,    5,
+     11,   13,   13,   11,   -3,    3,    8,    3,
+      7,    7,    7,    5,    4,   -3,   -5,   -3,
+      4,    3,   13,    1,    2,    1,   -1,    2,
+      3,    5,    8,    4,   -5,   -6,   -8,  -11,
+     -4,    0,   -5,   -1,   -7,  -12,   -8,  -16,
+     -6,   -6,    0,    2,   -9,   -9,  -11,   -3,
+     -9,    2,    3,   -1,   -5,  -13,    4,  -20,
+  ],
+  [
+    -58,  -38,  -13,  -28,  -31,  -27,  -63,  -99,
+    -25,   -8,  -25,   -2,   -9,  -25,  -24,  -52,
+    -24,  -20,   10,    9,   -1,   -9,  -19,  -41,
+    -17,    3,   22,   22,   22,   11,    8,  -18,
+    -18,   -6,   16,   25,   16,   17,    4,  -18,
+    -23,   -3,   -1,   15,   10,   -3,  -20,  -22,
+    -42,  -20,  -10,   -5,   -2,  -20,  -23,  -44,
+    -29,  -51,  -23,  -15,  -22,  -18,  -50,  -64,
+  ],
+  [
+    -14,  -21,  -11,   -8,   -7,   -9,  -17,  -24,
+     -8,   -4,    7,  -12,   -3,  -13,   -4,  -14,
+      2,   -8,    0,   -1,   -2,    6,    0,    4,
+     -3,    9,   12,    9,   14,   10,    3,    2,
+     -6,    3,   13,   19,    7,   10,   -3,   -9,
+    -12,   -3,    8,   10,   13,    3,   -7,  -15,
+    -14,  -18,   -7,   -1,    4,   -9,  -15,  -27,
+    -23,   -9,  -23,   -5,   -9,  -16,   -5,  -17,
+  ],
+  [
+     -9,   22,   22,   27,   27,   19,   10,   20,
+    -17,   20,   32,   41,   58,   25,   30,    0,
+    -20,    6,    9,   49,   47,   35,   19,    9,
+      3,   22,   24,   45,   57,   40,   57,   36,
+    -18,   28,   19,   47,   31,   34,   39,   23,
+    -16,  -27,   15,    6,    9,   17,   10,    5,
+    -22,  -23,  -30,  -16,  -16,  -23,  -36,  -32,
+    -33,  -28,  -22,  -43,   -5,  -32,  -20,  -41,
+  ],
+  [
+    -74,  -35,  -18,  -18,  -11,   15,    4,  -17,
+    -12,   17,   14,   17,   17,   38,   23,   11,
+     10,   17,   23,   15,   20,   45,   44,   13,
+     -8,   22,   24,   27,   26,   33,   26,    3,
+    -18,   -4,   21,   24,   27,   23,    9,  -11,
+    -19,   -3,   11,   21,   23,   16,    7,   -9,
+    -27,  -11,    4,   13,   14,    4,   -5,  -17,
+    -53,  -34,  -21,  -11,  -28,  -14,  -24,  -43,
+  ],
+];
+
+// Every weight of the evaluation, in pawns, with a middlegame and an endgame value each. The defaults are what
+// the engine plays with unless it is given a parameter file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EvalParams {
+  // indexed by piece kind as in bitboard
+  pub piece_values: [TaperedScore; 6],
+  // Laid out as the board is seen from white's side, a8 first, so a white piece on square s reads entry s ^ 56
+  // and a black piece on s reads entry s, which mirrors the table for black.
+  pub piece_squares: [[TaperedScore; 64]; 6],
+  // per square a piece attacks that isn't taken by its own pieces or covered by enemy pawns
+  pub mobility: [TaperedScore; 6],
+  pub doubled_pawn: TaperedScore,
+  pub isolated_pawn: TaperedScore,
+  pub backward_pawn: TaperedScore,
+  // by the rank a passed pawn has reached, counted from its own side
+  pub passed_pawn: [TaperedScore; 8],
+  // for each of the king's own pawns on its file and the files next to it, one or two ranks in front
+  pub pawn_shield_close: TaperedScore,
+  pub pawn_shield_far: TaperedScore,
+  // per square next to the enemy king a piece attacks, by the kind of piece
+  pub king_attack: [TaperedScore; 6],
+  pub bishop_pair: TaperedScore,
+  pub rook_open_file: TaperedScore,
+  pub rook_semi_open_file: TaperedScore,
+  // a knight or bishop in the enemy half, defended by a pawn, on a square no enemy pawn can ever attack
+  pub knight_outpost: TaperedScore,
+  pub bishop_outpost: TaperedScore,
+}
+
+impl Default for EvalParams {
+  fn default() -> EvalParams {
+    let mut params = EvalParams{
+      piece_values: [TaperedScore::default(); 6],
+      piece_squares: [[TaperedScore::default(); 64]; 6],
+      mobility: [TaperedScore::default(); 6],
+      doubled_pawn: create_tapered_score(-0.10, -0.25),
+      isolated_pawn: create_tapered_score(-0.15, -0.20),
+      backward_pawn: create_tapered_score(-0.10, -0.15),
+      passed_pawn: [TaperedScore::default(); 8],
+      pawn_shield_close: create_tapered_score(0.12, 0.0),
+      pawn_shield_far: create_tapered_score(0.06, 0.0),
+      king_attack: [TaperedScore::default(); 6],
+      bishop_pair: create_tapered_score(0.30, 0.50),
+      rook_open_file: create_tapered_score(0.25, 0.10),
+      rook_semi_open_file: create_tapered_score(0.10, 0.05),
+      knight_outpost: create_tapered_score(0.30, 0.20),
+      bishop_outpost: create_tapered_score(0.15, 0.10),
+    };
+    for kind in 0..6 {
+      params.piece_values[kind] = create_tapered_score(DEFAULT_MIDDLEGAME_VALUES[kind] as f32 / 100.0, DEFAULT_ENDGAME_VALUES[kind] as f32 / 100.0);
+      for square in 0..64 {
+        params.piece_squares[kind][square] = create_tapered_score(DEFAULT_MIDDLEGAME_TABLES[kind][square] as f32 / 100.0, DEFAULT_ENDGAME_TABLES[kind][square] as f32 / 100.0);
+      }
+    }
+    let mobility = [(0.02, 0.04), (0.04, 0.04), (0.05, 0.05), (0.01, 0.02)];
+    let king_attack = [0.08, 0.06, 0.06, 0.10];
+    for (i, kind) in PIECE_KINDS.iter().enumerate() {
+      params.mobility[*kind] = create_tapered_score(mobility[i].0, mobility[i].1);
+      params.king_attack[*kind] = create_tapered_score(king_attack[i], 0.0);
+    }
+    let passed_middlegame = [0.0, 0.05, 0.10, 0.15, 0.30, 0.50, 0.80, 0.0];
+    let passed_endgame = [0.0, 0.10, 0.20, 0.35, 0.60, 1.00, 1.50, 0.0];
+    for rank in 0..8 {
+      params.passed_pawn[rank] = create_tapered_score(passed_middlegame[rank], passed_endgame[rank]);
+    }
+    return params;
+  }
+}
+
+impl EvalParams {
+  // Every weight under the name it has in a parameter file, a single weight being a slice of one. Going
+  // through this is how the file is read and written, so a weight that isn't listed here can't be set.
+  pub fn get_fields(&mut self) -> Vec<(String, &mut [TaperedScore])> {
+    let mut fields: Vec<(String, &mut [TaperedScore])> = Vec::new();
+    for (kind, value) in self.piece_values.iter_mut().enumerate() {
+      fields.push((format!("{}_value", KIND_NAMES[kind]), std::slice::from_mut(value)));
+    }
+    for (kind, table) in self.piece_squares.iter_mut().enumerate() {
+      fields.push((format!("{}_squares", KIND_NAMES[kind]), &mut table[..]));
+    }
+    for (kind, weight) in self.mobility.iter_mut().enumerate() {
+      if PIECE_KINDS.contains(&kind) {
+        fields.push((format!("{}_mobility", KIND_NAMES[kind]), std::slice::from_mut(weight)));
+      }
+    }
+    for (kind, weight) in self.king_attack.iter_mut().enumerate() {
+      if PIECE_KINDS.contains(&kind) {
+        fields.push((format!("{}_king_attack", KIND_NAMES[kind]), std::slice::from_mut(weight)));
+      }
+    }
+    fields.push(("doubled_pawn".to_string(), std::slice::from_mut(&mut self.doubled_pawn)));
+    fields.push(("isolated_pawn".to_string(), std::slice::from_mut(&mut self.isolated_pawn)));
+    fields.push(("backward_pawn".to_string(), std::slice::from_mut(&mut self.backward_pawn)));
+    fields.push(("passed_pawn".to_string(), &mut self.passed_pawn[..]));
+    fields.push(("pawn_shield_close".to_string(), std::slice::from_mut(&mut self.pawn_shield_close)));
+    fields.push(("pawn_shield_far".to_string(), std::slice::from_mut(&mut self.pawn_shield_far)));
+    fields.push(("bishop_pair".to_string(), std::slice::from_mut(&mut self.bishop_pair)));
+    fields.push(("rook_open_file".to_string(), std::slice::from_mut(&mut self.rook_open_file)));
+    fields.push(("rook_semi_open_file".to_string(), std::slice::from_mut(&mut self.rook_semi_open_file)));
+    fields.push(("knight_outpost".to_string(), std::slice::from_mut(&mut self.knight_outpost)));
+    fields.push(("bishop_outpost".to_string(), std::slice::from_mut(&mut self.bishop_outpost)));
+    return fields;
+  }
+
+  // the higher of a piece kind's two values, for the parts of the search that only need to know what a
+  // capture could be worth
+  pub fn get_piece_value(&self, kind: usize) -> f32 {
+    let value = self.piece_values[kind % 6];
+    return value.middlegame.max(value.endgame);
+  }
+
+  // The parameters as a file parse_eval_params reads back. The format is a small part of TOML: each weight
+  // has a name.middlegame and a name.endgame key, and tables are arrays, eight to a line.
+  pub fn to_string(&self) -> String {
+    let mut params = self.clone();
+    let mut lines = vec![
+      "# evaluation parameters in pawns, from the side of the player they apply to".to_string(),
+      "# tables are laid out as the board is seen from white, a8 first".to_string(),
+    ];
+    for (name, weights) in params.get_fields() {
+      for phase in ["middlegame", "endgame"] {
+        let values: Vec<String> = weights.iter().map(|w| format!("{}", if phase == "middlegame" { w.middlegame } else { w.endgame })).collect();
+        if values.len() == 1 {
+          lines.push(format!("{}.{} = {}", name, phase, values[0]));
+        } else {
+          lines.push(format!("{}.{} = [", name, phase));
+          for row in values.chunks(8) {
+            lines.push(format!("  {},", row.join(", ")));
+          }
+          lines.push("]".to_string());
+        }
+      }
+    }
+    return lines.join("\n") + "\n";
+  }
+
+  pub fn save(&self, path: &str) -> io::Result<()> {
+    return fs::write(path, self.to_string());
+  }
+}
+
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub enum ParamsErrorKind {
+  Io(String),
+  // not a key = value line
+  Malformed,
+  // the file ended inside an array
+  UnclosedArray,
+  UnknownKey(String),
+  InvalidNumber(String),
+  WrongLength { key: String, expected: usize, found: usize },
+}
+
+// line is the 1-based line of the file where the problem was found, 0 when the file couldn't be read
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub struct ParamsError {
+  pub line: usize,
+  pub kind: ParamsErrorKind,
+}
+
+impl fmt::Display for ParamsError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    return match &self.kind {
+      ParamsErrorKind::Io(message) => write!(f, "{}", message),
+      ParamsErrorKind::Malformed => write!(f, "expected key = value at line {}", self.line),
+      ParamsErrorKind::UnclosedArray => write!(f, "array opened at line {} is never closed", self.line),
+      ParamsErrorKind::UnknownKey(key) => write!(f, "unknown parameter {} at line {}", key, self.line),
+      ParamsErrorKind::InvalidNumber(value) => write!(f, "invalid number {} at line {}", value, self.line),
+      ParamsErrorKind::WrongLength{ key, expected, found } => write!(f, "expected {} values for {} but found {} at line {}", expected, key, found, self.line),
+    };
+  }
+}
+
+// Reads parameters written by EvalParams::to_string. Weights the text leaves out keep their default values, so a
+// file only needs the ones that differ.
+pub fn parse_eval_params(text: &str) -> Result<EvalParams, ParamsError> {
+  let mut params = EvalParams::default();
+  let mut fields = params.get_fields();
+  let mut lines = text.lines().enumerate();
+  while let Some((index, line)) = lines.next() {
+    let line_number = index + 1;
+    let error = |kind| ParamsError{ line: line_number, kind: kind };
+    let line = line.split('#').next().unwrap().trim();
+    if line.is_empty() {
+      continue;
+    }
+    let (key, value) = match line.split_once('=') {
+      Some((key, value)) => (key.trim(), value.trim().to_string()),
+      None => return Err(error(ParamsErrorKind::Malformed)),
+    };
+    // an array carries on over the following lines until it is closed
+    let mut value = value;
+    if value.starts_with('[') {
+      while !value.ends_with(']') {
+        match lines.next() {
+          Some((_, next)) => value.push_str(next.split('#').next().unwrap().trim()),
+          None => return Err(error(ParamsErrorKind::UnclosedArray)),
+        }
+      }
+      value = value[1..value.len() - 1].to_string();
+    }
+    let mut numbers = Vec::new();
+    for number in value.split(',').map(|n| n.trim()).filter(|n| !n.is_empty()) {
+      match number.parse::<f32>() {
+        Ok(n) => numbers.push(n),
+        Err(_) => return Err(error(ParamsErrorKind::InvalidNumber(number.to_string()))),
+      }
+    }
+    let (name, phase) = key.rsplit_once('.').unwrap_or((key, ""));
+    let weights = match fields.iter_mut().find(|(field, _)| field == name) {
+      Some((_, weights)) if phase == "middlegame" || phase == "endgame" => weights,
+      _ => return Err(error(ParamsErrorKind::UnknownKey(key.to_string()))),
+    };
+    if numbers.len() != weights.len() {
+      return Err(error(ParamsErrorKind::WrongLength{ key: key.to_string(), expected: weights.len(), found: numbers.len() }));
+    }
+    for (weight, number) in weights.iter_mut().zip(numbers) {
+      if phase == "middlegame" {
+        weight.middlegame = number;
+      } else {
+        weight.endgame = number;
+      }
+    }
+  }
+  return Ok(params);
+}
+
+pub fn load_eval_params(path: &str) -> Result<EvalParams, ParamsError> {
+  return match fs::read_to_string(path) {
+    Ok(text) => parse_eval_params(&text),
+    Err(e) => Err(ParamsError{ line: 0, kind: ParamsErrorKind::Io(format!("couldn't read {}: {}", path, e)) }),
+  };
+}
+
+// the defaults, shared, for whoever doesn't load parameters of their own
+pub fn create_default_params() -> Arc<EvalParams> {
+  return Arc::new(EvalParams::default());
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::chess_scoring::create_tapered_score;
+  use super::{EvalParams, ParamsError, ParamsErrorKind, load_eval_params, parse_eval_params};
+
+  fn parse_error(text: &str) -> ParamsError {
+    return parse_eval_params(text).err().unwrap();
+  }
+
+  #[test]
+  fn round_trips() {
+    assert_eq!(parse_eval_params(&EvalParams::default().to_string()).unwrap(), EvalParams::default());
+    let mut params = EvalParams::default();
+    params.piece_squares[2][27] = create_tapered_score(0.1 + 0.2, -1e-7);
+    params.bishop_pair = create_tapered_score(1.0 / 3.0, 123.456);
+    params.passed_pawn[6].endgame = -0.0;
+    assert_eq!(parse_eval_params(&params.to_string()).unwrap(), params);
+  }
+
+  #[test]
+  fn saves_and_loads() {
+    let path = std::env::temp_dir().join(format!("eval_params_test_{}.toml", std::process::id()));
+    let path = path.to_str().unwrap();
+    let mut params = EvalParams::default();
+    params.rook_open_file.middlegame = 0.5;
+    params.save(path).unwrap();
+    let loaded = load_eval_params(path);
+    let _ = std::fs::remove_file(path);
+    assert_eq!(loaded.unwrap(), params);
+  }
+
+  #[test]
+  fn missing_keys_keep_their_defaults() {
+    let text = "# only what differs\n\nknight_value.middlegame = 3.5   # a comment\npassed_pawn.endgame = [\n  0, 1, 2, 3,\n  4, 5, 6, 7,  # trailing comma\n]\n";
+    let params = parse_eval_params(text).unwrap();
+    let mut expected = EvalParams::default();
+    expected.piece_values[2].middlegame = 3.5;
+    for rank in 0..8 {
+      expected.passed_pawn[rank].endgame = rank as f32;
+    }
+    assert_eq!(params, expected);
+    assert_eq!(parse_eval_params("").unwrap(), EvalParams::default());
+  }
+
+  #[test]
+  fn errors() {
+    assert_eq!(parse_error("\nknight_value.middlegame 3"), ParamsError{ line: 2, kind: ParamsErrorKind::Malformed });
+    assert_eq!(parse_error("bishop_pair.middlegame = 0.3\npassed_pawn.endgame = [\n  0, 1, 2,\n"), ParamsError{ line: 2, kind: ParamsErrorKind::UnclosedArray });
+    assert_eq!(parse_error("queen_value = 9").kind, ParamsErrorKind::UnknownKey("queen_value".to_string()));
+    assert_eq!(parse_error("queen_value.opening = 9").kind, ParamsErrorKind::UnknownKey("queen_value.opening".to_string()));
+    assert_eq!(parse_error("pawn_mobility.middlegame = 0.1").kind, ParamsErrorKind::UnknownKey("pawn_mobility.middlegame".to_string()));
+    assert_eq!(parse_error("rook_value.endgame = five").kind, ParamsErrorKind::InvalidNumber("five".to_string()));
+    assert_eq!(parse_error("rook_value.endgame = [5, 6]").kind, ParamsErrorKind::WrongLength{ key: "rook_value.endgame".to_string(), expected: 1, found: 2 });
+    // lines are counted through the arrays before the problem
+    let text = "passed_pawn.middlegame = [\n  0, 0, 0, 0,\n  0, 0, 0, 0,\n]\npassed_pawn.endgame = [0, 0]\n";
+    assert_eq!(parse_error(text), ParamsError{ line: 5, kind: ParamsErrorKind::WrongLength{ key: "passed_pawn.endgame".to_string(), expected: 8, found: 2 } });
+    assert_eq!(load_eval_params("/nonexistent/eval_params.toml").err().unwrap().line, 0);
+  }
+
+  #[test]
+  fn messages() {
+    assert_eq!(parse_error("x").to_string(), "expected key = value at line 1");
+    assert_eq!(parse_error("\nknight_squares.middlegame = [1,").to_string(), "array opened at line 2 is never closed");
+    assert_eq!(parse_error("foo.endgame = 1").to_string(), "unknown parameter foo.endgame at line 1");
+    assert_eq!(parse_error("bishop_pair.endgame = 1..2").to_string(), "invalid number 1..2 at line 1");
+    assert_eq!(parse_error("knight_squares.endgame = [1, 2]").to_string(), "expected 64 values for knight_squares.endgame but found 2 at line 1");
+  }
+}
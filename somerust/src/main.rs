@@ -18,17 +18,52 @@ pub mod chess_color;
 pub mod thought_node;
 pub mod chess_scoring;
 pub mod multi_thought_node;
+pub mod pgn;
+pub mod chess_game;
+pub mod zobrist;
+pub mod bitboard;
+pub mod perft;
+pub mod transposition_table;
+pub mod search;
+pub mod move_ordering;
+pub mod quiescence;
+pub mod negamax;
+pub mod engine;
+pub mod pawn_table;
+pub mod eval_params;
+pub mod tuner;
 
 use chess_square::{ChessSquare};
 use rand::Rng;
-use std::{collections::{HashMap}};
-use thought_node::ThoughtNode;
-use multi_thought_node::MultiThoughtNode;
+use std::{collections::{HashMap}, sync::Arc};
+use engine::{EngineCommand, EngineEvent};
+use search::{SearchLimits, SearchOptions};
+use eval_params::EvalParams;
 use std::time;
 
-use crate::{castling::Castling, chess_board::{ChessBoard, copy_board}, chess_color::ChessColor, chess_move::{ChessMove, get_check, undo_move}};
+use crate::{castling::Castling, chess_color::ChessColor, chess_game::ChessGame, chess_move::{ChessMove, get_check}};
+
+// where the window looks for evaluation parameters when E is pressed, unless it was started with --params
+const DEFAULT_PARAMS_PATH: &str = "./eval_params.toml";
 
 fn main() {
+  let mut args: Vec<String> = std::env::args().skip(1).collect();
+  let params_path = take_params_path(&mut args);
+  let eval_params = match params_path.as_ref() {
+    Some(path) => match eval_params::load_eval_params(path) {
+      Ok(params) => Arc::new(params),
+      Err(e) => {
+        print!("invalid parameters in {}: {}\n", path, e);
+        return;
+      }
+    },
+    None => eval_params::create_default_params(),
+  };
+  if !args.is_empty() {
+    run_command(&args, &eval_params);
+    return;
+  }
+
   let mut rng = rand::thread_rng();
   let sdl = sdl2::init().unwrap();
   let video_subsystem = sdl.video().unwrap();
@@ -116,11 +151,20 @@ fn main() {
   sprites.insert(ChessSquare::BlackQueen, sprite::create_sprite("./imagery/chess_pieces/black_queen_120.png", pixels_per_unit, &gl));
   sprites.insert(ChessSquare::BlackRook, sprite::create_sprite("./imagery/chess_pieces/black_rook_120.png", pixels_per_unit, &gl));
 
-  let _fen1 = String::from("RNBQK1NR/PPPP1PPP/8/4P3/8/B7/1ppppppp/rnbqkbnr b KQkq - 0 3");
-  // let mut chess_board = chess_board::from_forsyth_edwards_notation(&fen1);
-  let mut chess_board = chess_board::create_new_board();
+  let _fen1 = String::from("rnbqkbnr/1ppppppp/B7/8/4P3/8/PPPP1PPP/RNBQK1NR b KQkq - 0 3");
+  // let mut game = chess_game::create_game(chess_board::from_forsyth_edwards_notation(&fen1).unwrap());
+  let mut game = chess_game::create_game(chess_board::create_new_board());
+  // the engine keeps its table for the whole game so it reuses what it worked out on earlier moves
+  let search_threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+  let mut search_options = SearchOptions{ eval_params: eval_params, ..Default::default() };
+  let engine = engine::create_engine_handle(64, search_threads, search_options.clone());
+  let computer_limits = SearchLimits{ movetime: Some(time::Duration::from_millis(2000)), ..Default::default() };
+  // the position the engine is searching for its move, and while the human thinks, the position it left them
+  // along with the one it is pondering on
+  let mut searching_hash: Option<u64> = None;
+  let mut pondering_hashes: Option<(u64, u64)> = None;
+  let mut search_start = time::Instant::now();
 
-  let mut game_moves: Vec<ChessMove> = Vec::new();
   let mut move_start_coords : Option<(usize, usize)> = None;
 
   unsafe {
@@ -140,8 +184,8 @@ fn main() {
                   let (ux, uy) = get_unit_coords(x, y, screen_width, screen_height, pixels_per_unit);
                   if -4.0 < ux && ux < 4.0 && -4.0 < uy && uy < 4.0 {
                     let (bx, by) = get_board_coords((ux, uy));
-                    let square = &chess_board.squares[by][bx];
-                    if square != &ChessSquare::Empty && square.get_color() == chess_board.current_player {
+                    let square = &game.board.squares[by][bx];
+                    if square != &ChessSquare::Empty && square.get_color() == game.board.current_player {
                       move_start_coords = Some((bx, by));
                     }
                   }
@@ -152,11 +196,11 @@ fn main() {
                     let (bx, by) = get_board_coords((ux, uy));
                     match move_start_coords {
                       Some(c) => {
-                        let potential_capture = &chess_board.squares[by][bx];
-                        if potential_capture == &ChessSquare::Empty || potential_capture.get_color() == chess_board.current_player.get_opposite() {
-                          let potential_move: ChessMove = if &chess_board.squares[c.1][c.0] == &ChessSquare::WhiteKing && (bx as i32 - c.0 as i32).abs() == 2 {
+                        let potential_capture = &game.board.squares[by][bx];
+                        if potential_capture == &ChessSquare::Empty || potential_capture.get_color() == game.board.current_player.get_opposite() {
+                          let potential_move: ChessMove = if &game.board.squares[c.1][c.0] == &ChessSquare::WhiteKing && (bx as i32 - c.0 as i32).abs() == 2 {
                             ChessMove{
-                              piece: chess_board.squares[c.1][c.0].clone(),
+                              piece: game.board.squares[c.1][c.0].clone(),
                               x: c.0,
                               y: c.1,
                               to_x: bx,
@@ -168,28 +212,28 @@ fn main() {
                             }
                           } else {
                             ChessMove{
-                              piece: chess_board.squares[c.1][c.0].clone(),
+                              piece: game.board.squares[c.1][c.0].clone(),
                               x: c.0,
                               y: c.1,
                               to_x: bx,
                               to_y: by,
                               capture: if potential_capture == &ChessSquare::Empty { None } else { Some(potential_capture.clone()) },
-                              promotion: if chess_board.squares[c.1][c.0] == ChessSquare::WhitePawn && by == 7 { Some(ChessSquare::WhiteQueen) } else { None },
+                              promotion: if game.board.squares[c.1][c.0] == ChessSquare::WhitePawn && by == 7 { Some(ChessSquare::WhiteQueen) } else { None },
                               castling: None,
                               en_pessant: false,
                             }
                           };
                           
-                          let valid_moves = chess_board.get_valid_moves();
+                          let valid_moves = game.board.get_valid_moves();
                           if valid_moves.contains(&potential_move) {
-                            chess_board = chess_board.do_move(&potential_move);
-                            print!("{}. {} ", (chess_board.move_number / 2 + 1), potential_move.to_string());
+                            print!("{}. {} ", game.board.fullmove_number, potential_move.to_san(&game.board));
+                            game.do_move(&potential_move);
+                            print_game_state(&game);
                             computer_can_move_instant = time::Instant::now() + time::Duration::from_millis(200);
-                            game_moves.push(potential_move.clone());
                           } else {
                             print!("{} was not valid {} {}! Trying en pessant.", potential_move.to_string(), potential_move.capture.is_some(), potential_move.promotion.is_some());
                             let test_en_pessant = ChessMove{
-                              piece: chess_board.squares[c.1][c.0].clone(),
+                              piece: game.board.squares[c.1][c.0].clone(),
                               x: c.0,
                               y: c.1,
                               to_x: bx,
@@ -200,10 +244,10 @@ fn main() {
                               en_pessant: true,
                             };
                             if valid_moves.contains(&test_en_pessant) {
-                              chess_board = chess_board.do_move(&test_en_pessant);
-                              print!("{}. {} ", (chess_board.move_number / 2 + 1), test_en_pessant.to_string());
+                              print!("{}. {} ", game.board.fullmove_number, test_en_pessant.to_san(&game.board));
+                              game.do_move(&test_en_pessant);
+                              print_game_state(&game);
                               computer_can_move_instant = time::Instant::now() + time::Duration::from_millis(200);
-                              game_moves.push(test_en_pessant.clone());
                             }
                           }
                         }
@@ -219,13 +263,13 @@ fn main() {
                       match code {
                         sdl2::keyboard::Keycode::M => {
                           print!("====valid moves====\n");
-                          for m in chess_board.get_valid_moves().iter() {
-                            print!("{}\n", m.to_string());
+                          for m in game.board.get_valid_moves().iter() {
+                            print!("{}\n", m.to_san(&game.board));
                           }
                           print!("===================\n");
                         }
                         sdl2::keyboard::Keycode::G => {
-                          match get_check(&chess_board.squares, &ChessColor::Black).or(get_check(&chess_board.squares, &ChessColor::White)) {
+                          match get_check(&game.board.squares, &ChessColor::Black).or(get_check(&game.board.squares, &ChessColor::White)) {
                             Some(c) => {
                               match c {
                                 ChessColor::Black => { print!("black is in check\n") }
@@ -236,36 +280,54 @@ fn main() {
                           }
                         }
                         sdl2::keyboard::Keycode::F => {
-                          print!("{}\n", chess_board.get_forsyth_edwards_notation());
+                          print!("{}\n", game.board.get_forsyth_edwards_notation());
+                        }
+                        sdl2::keyboard::Keycode::P => {
+                          let mut pgn_game = pgn::create_pgn_game(game.starting_position.clone());
+                          pgn_game.set_tag("White", "Human");
+                          pgn_game.set_tag("Black", if active_computer { "somerust" } else { "Human" });
+                          pgn_game.moves = game.moves.clone();
+                          if let Some(outcome) = game.get_outcome() {
+                            pgn_game.result = outcome.get_pgn_result();
+                          }
+                          let game_pgn = pgn_game.get_pgn();
+                          print!("{}\n", game_pgn);
+                          match std::fs::write("./last_game.pgn", &game_pgn) {
+                            Ok(_) => print!("saved to ./last_game.pgn\n"),
+                            Err(e) => print!("could not save game: {}\n", e),
+                          }
+                        }
+                        sdl2::keyboard::Keycode::S => {
+                          // move now with the best found so far
+                          if searching_hash.is_some() {
+                            engine.send(EngineCommand::Stop);
+                          }
+                        }
+                        sdl2::keyboard::Keycode::E => {
+                          // reload the evaluation parameters, which the engine uses from its next search on
+                          let path = params_path.as_deref().unwrap_or(DEFAULT_PARAMS_PATH);
+                          match eval_params::load_eval_params(path) {
+                            Ok(params) => {
+                              search_options.eval_params = Arc::new(params);
+                              engine.send(EngineCommand::SetOptions(search_options.clone()));
+                              print!("evaluating with the parameters in {}\n", path);
+                            }
+                            Err(e) => print!("could not load parameters: {}\n", e),
+                          }
                         }
                         sdl2::keyboard::Keycode::Escape => {
                           break 'main;
                         }
                         sdl2::keyboard::Keycode::Right => {
-                          let mut possible_moves = chess_board.get_valid_moves();
-                          let chosen_move = possible_moves.swap_remove(rng.gen_range(0..possible_moves.len()));
-                          chess_board = chess_board.do_move(&chosen_move);
-                          game_moves.push(chosen_move);
+                          let mut possible_moves = game.board.get_valid_moves();
+                          if !possible_moves.is_empty() {
+                            let chosen_move = possible_moves.swap_remove(rng.gen_range(0..possible_moves.len()));
+                            game.do_move(&chosen_move);
+                            print_game_state(&game);
+                          }
                         }
                         sdl2::keyboard::Keycode::Left => {
-                          let last_move = game_moves.pop();
-                          match last_move {
-                            Some(m) => {
-                              let mut board_copy = copy_board(&chess_board.squares);
-                              undo_move(&m, &mut board_copy);
-                              chess_board = ChessBoard{
-                                last_move: match game_moves.last() {
-                                  Some(m) => Some(m.clone()),
-                                  None => None,
-                                },
-                                squares: board_copy,
-                                current_player: chess_board.current_player.get_opposite(),
-                                available_castling: chess_board.available_castling,
-                                move_number: chess_board.move_number - 1
-                              };
-                            },
-                            _ => {}
-                          }
+                          game.undo_move();
                         }
                         _ => {}
                       }
@@ -277,31 +339,62 @@ fn main() {
             }
         }
         
-        if active_computer && chess_board.current_player == ChessColor::Black && time::Instant::now() > computer_can_move_instant {
-          let start = time::Instant::now();
-          // let mut multi_thought_node = MultiThoughtNode{
-          //   game_state: chess_board.clone(),
-          //   children: Vec::new(),
-          //   calculated_score: 0.0,
-          //   thought_threads: 30
-          // };
-          // let mut thought_node = multi_thought_node.alphabeta(4);
-          let mut thought_node = ThoughtNode{
-            game_state: chess_board.clone(),
-            children: Vec::new(),
-            calculated_score: 0.0,
-          };
-          thought_node.alphabeta(5);
-          if thought_node.children.is_empty() {
-            active_computer = false;
+        // A ponder search is only worth finishing while the human has yet to reply or has played the expected
+        // reply. Taking moves back, a random move, another reply or the end of the game leave it thinking about
+        // a position that won't come up, so it is stopped and its result ignored for being for another position.
+        if let Some((replying_hash, ponder_hash)) = pondering_hashes {
+          if game.get_outcome().is_some() || (game.board.hash != replying_hash && game.board.hash != ponder_hash) {
+            engine.send(EngineCommand::Stop);
+            pondering_hashes = None;
+          }
+        }
+
+        if active_computer && game.board.current_player == ChessColor::Black && time::Instant::now() > computer_can_move_instant && game.get_outcome().is_none() && searching_hash.is_none() {
+          search_start = time::Instant::now();
+          searching_hash = Some(game.board.hash);
+          if pondering_hashes.take().map(|(_, ponder_hash)| ponder_hash) == Some(game.board.hash) {
+            engine.send(EngineCommand::PonderHit);
           } else {
-            let best_ai_move = thought_node.get_best_move();
-            let stop = time::Instant::now();
-            chess_board = chess_board.do_move(&best_ai_move);
-            print!("{}\n", best_ai_move.to_string());
-            let thought_duration = stop - start;
-            print!("thought for {}\n", thought_duration.as_millis());
-            game_moves.push(best_ai_move.clone());
+            engine.send(EngineCommand::NewPosition(Box::new(game.clone())));
+            engine.send(EngineCommand::Go{ limits: computer_limits.clone(), ponder: false });
+          }
+        }
+
+        while let Some(event) = engine.try_recv() {
+          match event {
+            EngineEvent::Info(info) => print!("info {}\n", info),
+            EngineEvent::BestMove(key, result) => {
+              // a stopped ponder search reports on a position other than the one searched now
+              if searching_hash != Some(key) {
+                continue;
+              }
+              searching_hash = None;
+              // the game may have moved on, by taking moves back, since the search began
+              if key != game.board.hash {
+                continue;
+              }
+              if let Some(best_ai_move) = result.best_move {
+                print!("{}\n", best_ai_move.to_san(&game.board));
+                game.do_move(&best_ai_move);
+                print!("thought for {} to depth {} over {} nodes\n", search_start.elapsed().as_millis(), result.depth, result.nodes);
+                let table_stats = engine.table.get_stats();
+                print!("table hit rate {:.1}% with {} cutoffs\n", table_stats.get_hit_rate() * 100.0, table_stats.cutoffs);
+                print!("first move cutoff rate {:.1}%\n", result.ordering.get_first_move_cutoff_rate() * 100.0);
+                print_game_state(&game);
+                // think on the human's time about the reply the engine expects
+                if let Some(expected_reply) = result.pv.get(1) {
+                  if game.get_outcome().is_none() {
+                    let mut ponder_game = game.clone();
+                    ponder_game.do_move(expected_reply);
+                    pondering_hashes = Some((game.board.hash, ponder_game.board.hash));
+                    engine.send(EngineCommand::NewPosition(Box::new(ponder_game)));
+                    engine.send(EngineCommand::Go{ limits: computer_limits.clone(), ponder: true });
+                  }
+                }
+              } else {
+                active_computer = false;
+              }
+            }
           }
         }
 
@@ -327,12 +420,12 @@ fn main() {
 
           for y in 0..8 {
             for x in 0..8 {
-              if chess_board.squares[7 - y][x] != ChessSquare::Empty {
+              if game.board.squares[7 - y][x] != ChessSquare::Empty {
                 img_shader_program.set_used();
                 gl.ActiveTexture(gl::TEXTURE0 + 0);
-                gl.BindTexture(gl::TEXTURE_2D, sprites.get(&chess_board.squares[7 - y][x]).unwrap().tex.id);
+                gl.BindTexture(gl::TEXTURE_2D, sprites.get(&game.board.squares[7 - y][x]).unwrap().tex.id);
                 gl.Uniform1i(gl.GetUniformLocation(img_shader_program.id, tex_str.as_ptr()), 0);
-                gl.BindVertexArray(sprites.get(&chess_board.squares[7 - y][x]).unwrap().vao);
+                gl.BindVertexArray(sprites.get(&game.board.squares[7 - y][x]).unwrap().vao);
                 let translation_matrix = mat4::translation(-3.5 + x as f32, -3.5 + (7 - y) as f32, 0.0);
                 let m = mat4::col_mul(projection, translation_matrix);
                 gl.UniformMatrix4fv(gl.GetUniformLocation(img_shader_program.id, mvp_str.as_ptr()), 1, gl::FALSE, m.as_ptr());
@@ -359,4 +452,119 @@ fn get_board_coords(unit_coords: (f32, f32)) -> (usize, usize) {
   let bx = (unit_coords.0 + 3.5).round() as usize;
   let by = (unit_coords.1 + 3.5).round() as usize;
   return (bx, by);
+}
+
+fn print_game_state(game: &ChessGame) {
+  match game.get_outcome() {
+    Some(outcome) => print!("\n{} {}\n", outcome.to_string(), outcome.get_pgn_result()),
+    None => match game.get_claimable_draw() {
+      Some(reason) => print!("\na draw can be claimed ({:?})\n", reason),
+      None => {}
+    }
+  }
+}
+
+// takes `--params <file>` out of the arguments, wherever it is, so the window and the commands can both be given
+// evaluation parameters
+fn take_params_path(args: &mut Vec<String>) -> Option<String> {
+  let index = args.iter().position(|a| a == "--params")?;
+  args.remove(index);
+  if index < args.len() {
+    return Some(args.remove(index));
+  }
+  return None;
+}
+
+// command line tools that run without opening a window, e.g. `somerust perft 5` or `somerust divide 3 "<fen>"`
+fn run_command(args: &[String], eval_params: &EvalParams) {
+  let start_fen = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+  match args[0].as_str() {
+    "perft" | "divide" => {
+      let depth = match args.get(1).map(|d| d.parse::<u32>()) {
+        Some(Ok(d)) => d,
+        _ => {
+          print!("usage: {} <depth> [fen]\n", args[0]);
+          return;
+        }
+      };
+      let board = match chess_board::from_forsyth_edwards_notation(args.get(2).map_or(start_fen, |f| f.as_str())) {
+        Ok(b) => b,
+        Err(e) => {
+          print!("invalid fen: {}\n", e);
+          return;
+        }
+      };
+      let start = time::Instant::now();
+      let nodes = if args[0] == "divide" {
+        let mut divided = board.divide(depth);
+        divided.sort_by_key(|(m, _)| m.to_uci());
+        for (m, count) in divided.iter() {
+          print!("{}: {}\n", m.to_uci(), count);
+        }
+        divided.iter().map(|(_, count)| count).sum()
+      } else {
+        board.perft(depth)
+      };
+      print!("nodes: {}\ntime: {} ms\n", nodes, (time::Instant::now() - start).as_millis());
+    }
+    "eval" => {
+      match chess_board::from_forsyth_edwards_notation(args.get(1).map_or(start_fen, |f| f.as_str())) {
+        Ok(board) => print!("{}\n", chess_scoring::evaluate(&bitboard::from_chess_board(&board), eval_params).to_string()),
+        Err(e) => print!("invalid fen: {}\n", e),
+      }
+    }
+    // writes the parameters in use, the defaults unless --params was given, as a starting point for a file
+    "params" => {
+      match args.get(1) {
+        Some(path) => match eval_params.save(path) {
+          Ok(_) => print!("saved to {}\n", path),
+          Err(e) => print!("could not save parameters: {}\n", e),
+        },
+        None => print!("usage: params <file>\n"),
+      }
+    }
+    // Tunes the parameters in use, the defaults unless --params was given, against a file of positions labelled
+    // with game results. The parameters are saved after every pass and the report next to them at the end.
+    "tune" => {
+      let (positions_path, output_path) = match (args.get(1), args.get(2)) {
+        (Some(positions), Some(output)) => (positions, output),
+        _ => {
+          print!("usage: tune <positions file> <output file> [max passes]\n");
+          return;
+        }
+      };
+      let mut options = tuner::TuneOptions{ threads: std::thread::available_parallelism().map_or(1, |n| n.get()), ..Default::default() };
+      if let Some(passes) = args.get(3) {
+        match passes.parse::<usize>() {
+          Ok(p) => options.max_passes = p,
+          Err(_) => {
+            print!("invalid pass count {}\n", passes);
+            return;
+          }
+        }
+      }
+      let positions = match tuner::load_tuning_positions(positions_path) {
+        Ok(p) => p,
+        Err(e) => {
+          print!("could not load positions: {}\n", e);
+          return;
+        }
+      };
+      print!("tuning on {} positions with {} threads\n", positions.len(), options.threads);
+      let result = tuner::tune(&positions, eval_params, &options, &mut |pass, params, error| {
+        print!("pass {} error {:.6}\n", pass, error);
+        if let Err(e) = params.save(output_path) {
+          print!("could not save parameters: {}\n", e);
+        }
+      });
+      let report = result.to_string();
+      print!("{}\n", report);
+      let report_path = format!("{}.report", output_path);
+      match result.params.save(output_path).and_then(|_| std::fs::write(&report_path, report + "\n")) {
+        Ok(_) => print!("saved to {} and {}\n", output_path, report_path),
+        Err(e) => print!("could not save results: {}\n", e),
+      }
+    }
+    _ => print!("unknown command {}, expected perft, divide, eval, params or tune\n", args[0])
+  }
 }
\ No newline at end of file
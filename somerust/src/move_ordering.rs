@@ -0,0 +1,210 @@
+use crate::bitboard::{BitMove, KING, KNIGHT, NO_PIECE, PAWN, QUEEN, ROOK};
+
+// killers are kept for this many plies from the root, deeper nodes go without
+const MAX_KILLER_PLY: usize = 128;
+
+const PROMOTION_ORDER: i32 = 3_000_000;
+const CAPTURE_ORDER: i32 = 2_000_000;
+const FIRST_KILLER_ORDER: i32 = 1_900_000;
+const SECOND_KILLER_ORDER: i32 = 1_800_000;
+// quiet moves are ordered by their history score, which is kept below the killers
+const MAX_HISTORY: u32 = 1_000_000;
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct OrderingStats {
+  pub cutoffs: u64,
+  // cutoffs caused by the first move searched, which happen more often the better the ordering is
+  pub first_move_cutoffs: u64,
+}
+
+impl OrderingStats {
+  pub fn get_first_move_cutoff_rate(&self) -> f32 {
+    if self.cutoffs == 0 {
+      return 0.0;
+    }
+    return self.first_move_cutoffs as f32 / self.cutoffs as f32;
+  }
+}
+
+// What the search has learnt about which moves cause cutoffs: two killer moves per ply, the last quiet moves
+// to cut off there, and a history score per side, from square and to square for quiet moves in general.
+pub struct MoveOrdering {
+  killers: Vec<[Option<BitMove>; 2]>,
+  history: Vec<u32>,
+  pub stats: OrderingStats,
+}
+
+impl MoveOrdering {
+  // higher is searched first: promotions, then captures by most valuable victim and least valuable attacker,
+  // then killers, then the remaining quiet moves by history
+  pub fn get_move_order(&self, m: &BitMove, ply: usize) -> i32 {
+    if let Some(order) = get_capture_order(m) {
+      return order;
+    }
+    if let Some(killers) = self.killers.get(ply) {
+      if killers[0] == Some(*m) {
+        return FIRST_KILLER_ORDER;
+      }
+      if killers[1] == Some(*m) {
+        return SECOND_KILLER_ORDER;
+      }
+    }
+    return self.history[get_history_index(m)] as i32;
+  }
+
+  // move_index is where the move came in the order it was searched
+  pub fn record_cutoff(&mut self, m: &BitMove, ply: usize, depth: i32, move_index: usize) {
+    self.stats.cutoffs += 1;
+    if move_index == 0 {
+      self.stats.first_move_cutoffs += 1;
+    }
+    if m.capture != NO_PIECE || m.promotion != NO_PIECE {
+      return;
+    }
+    if let Some(killers) = self.killers.get_mut(ply) {
+      if killers[0] != Some(*m) {
+        killers[1] = killers[0];
+        killers[0] = Some(*m);
+      }
+    }
+    let index = get_history_index(m);
+    self.history[index] += (depth * depth) as u32;
+    if self.history[index] >= MAX_HISTORY {
+      // halving everything keeps the scores in range and lets newer cutoffs count for more
+      for h in self.history.iter_mut() {
+        *h /= 2;
+      }
+    }
+  }
+}
+
+// the order of a promotion or capture, which doesn't depend on what the search has seen so far
+pub fn get_capture_order(m: &BitMove) -> Option<i32> {
+  if m.promotion != NO_PIECE {
+    return Some(PROMOTION_ORDER + get_order_value(m.promotion) * 16 + get_order_value(m.capture));
+  }
+  if m.capture != NO_PIECE {
+    return Some(CAPTURE_ORDER + get_order_value(m.capture) * 16 - get_order_value(m.piece));
+  }
+  return None;
+}
+
+// Moves the best ordered of the moves from index on to index, so a node only sorts as far as it searches.
+// orders holds the order of each move and is swapped along with them.
+pub fn pick_next_move(moves: &mut [BitMove], orders: &mut [i32], index: usize) {
+  let mut best = index;
+  for i in index + 1..moves.len() {
+    if orders[i] > orders[best] {
+      best = i;
+    }
+  }
+  moves.swap(index, best);
+  orders.swap(index, best);
+}
+
+pub fn create_move_ordering() -> MoveOrdering {
+  return MoveOrdering{
+    killers: vec![[None, None]; MAX_KILLER_PLY],
+    history: vec![0; 2 * 64 * 64],
+    stats: OrderingStats::default(),
+  };
+}
+
+fn get_history_index(m: &BitMove) -> usize {
+  return ((m.piece as usize / 6) * 64 + m.from as usize) * 64 + m.to as usize;
+}
+
+fn get_order_value(piece: u8) -> i32 {
+  if piece == NO_PIECE {
+    return 0;
+  }
+  return match piece % 6 {
+    PAWN => 1,
+    KNIGHT => 3,
+    ROOK => 5,
+    QUEEN => 9,
+    KING => 10,
+    _ => 3,
+  };
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::bitboard::{BitMove, from_chess_move};
+  use crate::chess_board::{ChessBoard, from_forsyth_edwards_notation};
+  use super::{FIRST_KILLER_ORDER, SECOND_KILLER_ORDER, create_move_ordering, pick_next_move};
+
+  // killers are only kept for the plies near the root, so here a quiet move is ordered by its history alone
+  const DEEP_PLY: usize = 200;
+
+  fn get_board() -> ChessBoard {
+    return from_forsyth_edwards_notation("4k3/1P6/8/3q4/p3P3/8/8/Q3K3 w - - 0 1").unwrap();
+  }
+
+  fn get_move(board: &ChessBoard, uci: &str) -> BitMove {
+    return from_chess_move(&board.parse_uci(uci).unwrap());
+  }
+
+  #[test]
+  fn mvv_lva() {
+    let board = get_board();
+    let ordering = create_move_ordering();
+    let order = |uci: &str| -> i32 { return ordering.get_move_order(&get_move(&board, uci), 0); };
+    // promotions, then pawn takes queen before queen takes pawn, then quiet moves
+    assert!(order("b7b8q") > order("b7b8n"));
+    assert!(order("b7b8n") > order("e4d5"));
+    assert!(order("e4d5") > order("a1a4"));
+    assert!(order("a1a4") > order("e1f1"));
+  }
+
+  #[test]
+  fn killers() {
+    let board = get_board();
+    let mut ordering = create_move_ordering();
+    let first = get_move(&board, "e1f1");
+    let second = get_move(&board, "e1e2");
+    ordering.record_cutoff(&first, 3, 1, 1);
+    assert_eq!(ordering.get_move_order(&first, 3), FIRST_KILLER_ORDER);
+    // killers belong to their ply
+    assert!(ordering.get_move_order(&first, 4) < SECOND_KILLER_ORDER);
+    ordering.record_cutoff(&second, 3, 1, 0);
+    assert_eq!(ordering.get_move_order(&second, 3), FIRST_KILLER_ORDER);
+    assert_eq!(ordering.get_move_order(&first, 3), SECOND_KILLER_ORDER);
+    // the first killer cutting off again doesn't push out the second
+    ordering.record_cutoff(&second, 3, 1, 0);
+    assert_eq!(ordering.get_move_order(&first, 3), SECOND_KILLER_ORDER);
+    // nor does a capture, which is ordered well anyway
+    let capture = get_move(&board, "a1a4");
+    ordering.record_cutoff(&capture, 3, 1, 0);
+    assert_eq!(ordering.get_move_order(&second, 3), FIRST_KILLER_ORDER);
+    assert_eq!((ordering.stats.cutoffs, ordering.stats.first_move_cutoffs), (4, 3));
+  }
+
+  #[test]
+  fn history_halves_at_the_limit() {
+    let board = get_board();
+    let mut ordering = create_move_ordering();
+    let quiet = get_move(&board, "e1f1");
+    let other = get_move(&board, "a1b1");
+    ordering.record_cutoff(&quiet, DEEP_PLY, 10, 1);
+    assert_eq!(ordering.get_move_order(&quiet, DEEP_PLY), 100);
+    ordering.record_cutoff(&other, DEEP_PLY, 1000, 1);
+    assert_eq!(ordering.get_move_order(&other, DEEP_PLY), 500_000);
+    assert_eq!(ordering.get_move_order(&quiet, DEEP_PLY), 50);
+  }
+
+  #[test]
+  fn picks_the_best_ordered_move_next() {
+    let board = get_board();
+    let mut moves: Vec<BitMove> = ["e1f1", "a1a4", "b7b8q", "e4d5"].iter().map(|uci| get_move(&board, uci)).collect();
+    let mut orders = vec![0, 20, 40, 30];
+    pick_next_move(&mut moves, &mut orders, 0);
+    assert_eq!((moves[0], orders[0]), (get_move(&board, "b7b8q"), 40));
+    pick_next_move(&mut moves, &mut orders, 1);
+    assert_eq!((moves[1], orders[1]), (get_move(&board, "e4d5"), 30));
+    // the moves and orders are swapped together, so they still belong to each other
+    pick_next_move(&mut moves, &mut orders, 2);
+    assert_eq!((moves[2], orders[2]), (get_move(&board, "a1a4"), 20));
+    assert_eq!((moves[3], orders[3]), (get_move(&board, "e1f1"), 0));
+  }
+}
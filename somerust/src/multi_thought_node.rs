@@ -1,7 +1,10 @@
-use std::{cmp::{Ordering}, thread};
+use std::{sync::atomic::Ordering, thread, time::Instant};
 
-use crate::{chess_board::ChessBoard, chess_color::ChessColor, chess_move::{ChessMove, get_check}, chess_scoring::score_game_state, thought_node::ThoughtNode};
+use crate::{chess_board::ChessBoard, chess_game::{ChessGame, create_game}, search::{SearchInfo, SearchLimits, SearchOptions, SearchRequest, SearchResult, SharedSearch}, thought_node::ThoughtNode, transposition_table::{DEFAULT_TABLE_MEGABYTES, TranspositionTable}};
 
+// Lazy SMP: every thread searches the whole position, sharing bounds and best moves through one transposition
+// table, and the main thread's result is the one played. Helpers start one ply deeper on every other thread so
+// they are mostly a step ahead filling the table for the main thread.
 pub struct MultiThoughtNode {
   pub game_state: ChessBoard,
   pub children: Vec<ThoughtNode>,
@@ -11,35 +14,79 @@ pub struct MultiThoughtNode {
 
 impl MultiThoughtNode {
   pub fn alphabeta(&mut self, depth: i32) -> ThoughtNode {
-    let valid_moves = self.game_state.get_valid_moves();
-    let chunk_size = valid_moves.len() / self.thought_threads as usize;
-    let move_chunks : Vec<Vec<ChessMove>> = valid_moves.chunks(chunk_size).map(|x| x.to_vec()).collect();
-    let mut thread_handles = Vec::new();
-    for move_chunk in move_chunks {
-      let curr_state = self.game_state.clone();
-      thread_handles.push(thread::spawn(move || {
-        let mut thought_nodes = Vec::new();
-        for chess_move in move_chunk.iter() {
-          let state = curr_state.do_move(chess_move);
-          let mut thought_node = ThoughtNode{
-            game_state: state,
-            children: Vec::new(),
-            calculated_score: 0.0
-          };
-          thought_node.alphabeta(depth);
-          thought_nodes.push(thought_node);
-        }
-        return thought_nodes;
-      }));
-    }
-    let mut children : Vec<ThoughtNode> = Vec::new(); 
-    for th in thread_handles {
-      children.extend(th.join().unwrap());
-    }
+    let limits = SearchLimits{ max_depth: Some(depth), ..Default::default() };
+    let table = TranspositionTable::new(DEFAULT_TABLE_MEGABYTES);
+    self.think(&limits, &SearchOptions::default(), &create_game(self.game_state.clone()), &table, &mut |_| {});
     return ThoughtNode{
       game_state: self.game_state.clone(),
-      children: children,
-      calculated_score: 0.0
+      children: std::mem::take(&mut self.children),
+      calculated_score: self.calculated_score,
+    };
+  }
+
+  // the same search as ThoughtNode::think spread over thought_threads threads; the main thread's tree ends up
+  // in children and only the main thread reports info
+  pub fn think(&mut self, limits: &SearchLimits, options: &SearchOptions, game: &ChessGame, table: &TranspositionTable, on_info: &mut dyn FnMut(&SearchInfo)) -> SearchResult {
+    let start = Instant::now();
+    let request = SearchRequest{ game: game, limits: limits, options: options, table: table };
+    let shared = SharedSearch::default();
+    let mut main_node = ThoughtNode{
+      game_state: self.game_state.clone(),
+      children: std::mem::take(&mut self.children),
+      calculated_score: 0.0,
     };
+    let mut result = thread::scope(|scope| {
+      for helper in 1..self.thought_threads.max(1) {
+        let mut helper_node = ThoughtNode{
+          game_state: self.game_state.clone(),
+          children: Vec::new(),
+          calculated_score: 0.0,
+        };
+        let request = &request;
+        let shared = &shared;
+        scope.spawn(move || {
+          helper_node.think_shared(request, &mut |_| {}, Some(shared), 1 + (helper % 2) as i32);
+        });
+      }
+      let result = main_node.think_shared(&request, on_info, Some(&shared), 1);
+      shared.stop.store(true, Ordering::Relaxed);
+      return result;
+    });
+    result.nodes = shared.nodes.load(Ordering::Relaxed);
+    result.elapsed = Instant::now() - start;
+    self.children = main_node.children;
+    self.calculated_score = result.score;
+    return result;
   }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::chess_board::from_forsyth_edwards_notation;
+  use crate::chess_game::create_game;
+  use crate::search::{SearchLimits, SearchOptions};
+  use crate::thought_node::ThoughtNode;
+  use crate::transposition_table::TranspositionTable;
+  use super::MultiThoughtNode;
+
+  #[test]
+  fn threads_agree_with_one_thread_on_few_moves() {
+    // more threads than moves at the root
+    for (fen, best_move) in [
+      ("k7/8/8/8/8/8/1q6/K7 w - - 0 1", "a1b2"),
+      ("6k1/5ppp/8/8/8/8/r7/K3R3 w - - 0 1", "a1a2"),
+    ] {
+      let board = from_forsyth_edwards_notation(fen).unwrap();
+      assert!(board.get_valid_moves().len() <= 2);
+      let game = create_game(board.clone());
+      let limits = SearchLimits{ max_depth: Some(5), ..Default::default() };
+      let mut node = ThoughtNode{ game_state: board.clone(), children: Vec::new(), calculated_score: 0.0 };
+      let single = node.think(&limits, &SearchOptions::default(), &game, &TranspositionTable::new(16), &mut |_| {});
+      let mut multi_node = MultiThoughtNode{ game_state: board, children: Vec::new(), calculated_score: 0.0, thought_threads: 4 };
+      let multi = multi_node.think(&limits, &SearchOptions::default(), &game, &TranspositionTable::new(16), &mut |_| {});
+      assert_eq!(single.best_move.unwrap().to_uci(), best_move, "{}", fen);
+      assert_eq!(multi.best_move.unwrap().to_uci(), best_move, "{}", fen);
+      assert_eq!(multi.depth, 5, "{}", fen);
+    }
+  }
+}
@@ -0,0 +1,359 @@
+use std::{sync::atomic::Ordering, thread, time::Instant};
+
+use crate::{bitboard::{MAX_MOVES, MoveList, NO_PIECE, Position, WHITE, from_chess_board}, chess_game::ChessGame, chess_scoring::Evaluator, move_ordering::pick_next_move, quiescence::quiescence, search::{BLACK_WIN_SCORE, MATE_SCORE, SearchContext, SearchInfo, SearchLimits, SearchOptions, SearchRequest, SearchResult, SharedSearch, WHITE_WIN_SCORE, get_bound, is_mate_score, score_from_table, score_to_table, think_iteratively}, transposition_table::{Bound, TableEntry, TranspositionTable, get_move_code}};
+
+// a null move is searched this much shallower than a real one would be, more at greater depths
+const NULL_MOVE_REDUCTION: i32 = 2;
+const NULL_MOVE_MIN_DEPTH: i32 = 3;
+// from this depth a null-move cutoff is only taken once a reduced search without passing agrees
+const NULL_MOVE_VERIFICATION_DEPTH: i32 = 6;
+// width of the window used to ask only whether a score reaches beta
+const NULL_WINDOW: f32 = 0.01;
+// moves from this far down the order are reduced, and by two plies from twice as far
+const LATE_MOVE_INDEX: usize = 4;
+const LATE_MOVE_MIN_DEPTH: i32 = 3;
+// the most a quiet move is expected to gain at each remaining depth, indexed by depth
+const FUTILITY_MARGINS: [f32; 3] = [0.0, 1.5, 3.5];
+// half the first aspiration window in pawns, and the half-width beyond which the window is dropped entirely
+const ASPIRATION_WINDOW: f32 = 0.5;
+const MAX_ASPIRATION_WINDOW: f32 = 4.0;
+
+// The same search as ThoughtNode::think without building a tree: one position is made and unmade in place,
+// moves are generated into lists on the stack, and all that is kept is the principal variation. Scores inside
+// the search are from the side to move's point of view; the table and everything returned are from white's
+// like the rest of the engine.
+pub fn think(game: &ChessGame, limits: &SearchLimits, options: &SearchOptions, table: &TranspositionTable, on_info: &mut dyn FnMut(&SearchInfo)) -> SearchResult {
+  return think_parallel(game, limits, options, table, 1, on_info);
+}
+
+// think spread over threads threads as Lazy SMP, like MultiThoughtNode::think; only the main thread reports info
+pub fn think_parallel(game: &ChessGame, limits: &SearchLimits, options: &SearchOptions, table: &TranspositionTable, threads: usize, on_info: &mut dyn FnMut(&SearchInfo)) -> SearchResult {
+  return think_parallel_shared(game, limits, options, table, threads, &SharedSearch::default(), on_info);
+}
+
+// think_parallel with the shared state owned by the caller, which can set its stop flag from another thread to
+// end the search early; the best move of the last completed iteration is still returned
+pub fn think_parallel_shared(game: &ChessGame, limits: &SearchLimits, options: &SearchOptions, table: &TranspositionTable, threads: usize, shared: &SharedSearch, on_info: &mut dyn FnMut(&SearchInfo)) -> SearchResult {
+  let start = Instant::now();
+  let request = SearchRequest{ game: game, limits: limits, options: options, table: table };
+  let mut result = thread::scope(|scope| {
+    for helper in 1..threads.max(1) {
+      let request = &request;
+      scope.spawn(move || {
+        think_shared(request, &mut |_| {}, shared, 1 + (helper % 2) as i32);
+      });
+    }
+    let result = think_shared(&request, on_info, shared, 1);
+    shared.stop.store(true, Ordering::Relaxed);
+    return result;
+  });
+  result.nodes = shared.nodes.load(Ordering::Relaxed);
+  result.elapsed = Instant::now() - start;
+  return result;
+}
+
+fn think_shared(request: &SearchRequest, on_info: &mut dyn FnMut(&SearchInfo), shared: &SharedSearch, first_depth: i32) -> SearchResult {
+  let mut position = from_chess_board(&request.game.board);
+  let mut previous_score = None;
+  return think_iteratively(request, Some(shared), first_depth, on_info, &mut |context, depth| {
+    let score = search_root(&mut position, depth, previous_score, context);
+    // the root always has a line unless it has no moves, or the search was stopped before reaching it
+    let best_move = context.pv.first()?.first()?.to_chess_move();
+    previous_score = Some(score);
+    return Some((if position.side_to_move == WHITE { score } else { -score }, best_move));
+  });
+}
+
+// Searches the root, with a window around the last iteration's score when aspiration windows are on. A score
+// outside the window only says which side of it the true score is, so the window is widened on that side and
+// the root searched again.
+fn search_root(position: &mut Position, depth: i32, previous_score: Option<f32>, context: &mut SearchContext) -> f32 {
+  let mut delta = ASPIRATION_WINDOW;
+  let (mut alpha, mut beta) = match previous_score {
+    Some(score) if context.options.aspiration_windows && !is_mate_score(score) => (score - delta, score + delta),
+    _ => (BLACK_WIN_SCORE, WHITE_WIN_SCORE),
+  };
+  loop {
+    let score = negamax(position, depth, alpha, beta, context, true);
+    if context.stopped {
+      return score;
+    }
+    if score <= alpha && alpha > BLACK_WIN_SCORE {
+      alpha = score - delta;
+    } else if score >= beta && beta < WHITE_WIN_SCORE {
+      beta = score + delta;
+    } else {
+      return score;
+    }
+    delta *= 2.0;
+    if delta > MAX_ASPIRATION_WINDOW || is_mate_score(score) {
+      alpha = BLACK_WIN_SCORE;
+      beta = WHITE_WIN_SCORE;
+    }
+  }
+}
+
+// allow_null is false straight after a null move, so the two sides can't pass in turn
+fn negamax(position: &mut Position, mut depth: i32, mut alpha: f32, mut beta: f32, context: &mut SearchContext, allow_null: bool) -> f32 {
+  context.count_node();
+  if context.stopped {
+    return 0.0;
+  }
+  let ply = context.get_ply();
+  context.clear_pv(ply);
+  context.stats.seldepth = context.stats.seldepth.max(ply as i32);
+  let is_white = position.side_to_move == WHITE;
+  let entry = context.table.probe(position.hash);
+  if ply > 0 {
+    if position.halfmove_clock >= 100 || position.has_insufficient_material() || context.is_repetition(position.hash) {
+      return 0.0;
+    }
+    if let Some(e) = entry {
+      let score = score_from_table(e.score, ply);
+      let (score, bound) = if is_white { (score, e.bound) } else { (-score, get_flipped_bound(e.bound)) };
+      let usable = match bound {
+        Bound::Exact => true,
+        Bound::Lower => score >= beta,
+        Bound::Upper => score <= alpha,
+      };
+      if e.depth >= depth && usable {
+        context.table.record_cutoff();
+        return score;
+      }
+    }
+    // mate-distance pruning, as in ThoughtNode::alpha_beta_pruning
+    alpha = f32::max(alpha, -(MATE_SCORE - ply as f32));
+    beta = f32::min(beta, MATE_SCORE - ply as f32);
+    if alpha >= beta {
+      return alpha;
+    }
+  }
+  let in_check = position.is_in_check();
+  if in_check && context.options.check_extensions {
+    depth += 1;
+  }
+  // quiescence and the table work from white's side, so the window is turned round for black
+  let (white_alpha, white_beta) = if is_white { (alpha, beta) } else { (-beta, -alpha) };
+  if depth <= 0 {
+    let quiescence_depth = context.options.quiescence_depth;
+    let score = quiescence(position, white_alpha, white_beta, quiescence_depth, ply, &context.options, &mut context.evaluator, &mut context.stats);
+    context.table.store(position.hash, TableEntry{ depth: 0, bound: get_bound(score, white_alpha, white_beta), score: score_to_table(score, ply), best_move: 0 });
+    return if is_white { score } else { -score };
+  }
+
+  // the static score is only needed for pruning, and is slow enough to leave until then
+  let mut static_score = None;
+  let can_prune = ply > 0 && !in_check && !is_mate_score(alpha) && !is_mate_score(beta);
+
+  // Null-move pruning: if passing still leaves the score at least beta, a real move almost certainly would too.
+  // That fails in zugzwang, where every move makes things worse, so it isn't tried with only king and pawns
+  // left, and deep nodes check the cutoff with a reduced search that doesn't pass.
+  if context.options.null_move_pruning && allow_null && can_prune && depth >= NULL_MOVE_MIN_DEPTH && position.has_non_pawn_material(position.side_to_move) {
+    if get_static_score(position, &mut context.evaluator, &mut static_score) >= beta {
+      let reduction = NULL_MOVE_REDUCTION + depth / 6;
+      context.history.push(position.hash);
+      let undo = position.make_null_move();
+      let score = -negamax(position, depth - 1 - reduction, -beta, -beta + NULL_WINDOW, context, false);
+      context.history.pop();
+      position.unmake_null_move(undo);
+      if context.stopped {
+        return 0.0;
+      }
+      if score >= beta {
+        let verified = depth < NULL_MOVE_VERIFICATION_DEPTH || negamax(position, depth - 1 - reduction, beta - NULL_WINDOW, beta, context, false) >= beta;
+        if verified {
+          // a mate found after passing isn't a real one
+          return if is_mate_score(score) { beta } else { score };
+        }
+      }
+    }
+  }
+
+  let mut moves = MoveList::new();
+  position.generate_legal_moves(&mut moves);
+  if moves.is_empty() {
+    return if in_check { -(MATE_SCORE - ply as f32) } else { 0.0 };
+  }
+  // the table's best move first, as it is the most likely to cut off
+  let mut orders = [0; MAX_MOVES];
+  for (i, m) in moves.as_slice().iter().enumerate() {
+    orders[i] = if entry.map_or(false, |e| e.is_best_move(m)) { i32::MAX } else { context.ordering.get_move_order(m, ply) };
+  }
+  // Futility pruning: this close to the leaves a quiet move is unlikely to gain more than the margin, so when
+  // even that leaves the score at most alpha the move isn't searched.
+  let futility_score = if context.options.futility_pruning && can_prune && (depth as usize) < FUTILITY_MARGINS.len() {
+    Some(get_static_score(position, &mut context.evaluator, &mut static_score) + FUTILITY_MARGINS[depth as usize])
+  } else {
+    None
+  };
+
+  let mut best_score = BLACK_WIN_SCORE;
+  let mut best_move = 0;
+  context.history.push(position.hash);
+  for i in 0..moves.len() {
+    pick_next_move(moves.as_mut_slice(), &mut orders, i);
+    let m = moves.as_slice()[i];
+    let is_quiet = !m.is_capture() && m.promotion == NO_PIECE;
+    let undo = position.make_move(&m);
+    let gives_check = position.is_in_check();
+    if i > 0 && is_quiet && !gives_check && futility_score.map_or(false, |score| score <= alpha) {
+      position.unmake_move(&m, undo);
+      best_score = f32::max(best_score, futility_score.unwrap());
+      continue;
+    }
+    // Late move reductions: moves this far down the order rarely turn out best, so quiet ones are searched less
+    // deep, and only searched again at full depth when that says they beat alpha after all.
+    let reduction = if context.options.late_move_reductions && i >= LATE_MOVE_INDEX && depth >= LATE_MOVE_MIN_DEPTH && is_quiet && !in_check && !gives_check {
+      if i >= 2 * LATE_MOVE_INDEX && depth > LATE_MOVE_MIN_DEPTH { 2 } else { 1 }
+    } else {
+      0
+    };
+    let mut score = -negamax(position, depth - 1 - reduction, -beta, -alpha, context, true);
+    if reduction > 0 && score > alpha && !context.stopped {
+      score = -negamax(position, depth - 1, -beta, -alpha, context, true);
+    }
+    position.unmake_move(&m, undo);
+    if context.stopped {
+      break;
+    }
+    if score > best_score {
+      best_score = score;
+      best_move = get_move_code(&m);
+      if score > alpha || i == 0 {
+        context.update_pv(ply, &m);
+      }
+    }
+    alpha = f32::max(alpha, score);
+    if alpha >= beta {
+      context.ordering.record_cutoff(&m, ply, depth, i);
+      break;
+    }
+  }
+  context.history.pop();
+  if context.stopped {
+    return best_score;
+  }
+  let white_score = if is_white { best_score } else { -best_score };
+  let bound = get_bound(white_score, white_alpha, white_beta);
+  context.table.store(position.hash, TableEntry{ depth: depth, bound: bound, score: score_to_table(white_score, ply), best_move: best_move });
+  return best_score;
+}
+
+// the static score from the side to move's point of view, worked out the first time it's asked for
+fn get_static_score(position: &Position, evaluator: &mut Evaluator, static_score: &mut Option<f32>) -> f32 {
+  if static_score.is_none() {
+    let score = evaluator.score_position(position);
+    *static_score = Some(if position.side_to_move == WHITE { score } else { -score });
+  }
+  return static_score.unwrap();
+}
+
+// the same bound seen from the other side
+fn get_flipped_bound(bound: Bound) -> Bound {
+  return match bound {
+    Bound::Exact => Bound::Exact,
+    Bound::Lower => Bound::Upper,
+    Bound::Upper => Bound::Lower,
+  };
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::chess_board::from_forsyth_edwards_notation;
+  use crate::chess_game::create_game;
+  use crate::search::{MATE_SCORE, SearchLimits, SearchOptions, SearchResult};
+  use crate::transposition_table::TranspositionTable;
+  use crate::thought_node::ThoughtNode;
+  use super::{think, think_parallel};
+
+  fn search(fen: &str, depth: i32) -> SearchResult {
+    let game = create_game(from_forsyth_edwards_notation(fen).unwrap());
+    let limits = SearchLimits{ max_depth: Some(depth), ..Default::default() };
+    return think(&game, &limits, &SearchOptions::default(), &TranspositionTable::new(16), &mut |_| {});
+  }
+
+  #[test]
+  fn mate_in_n() {
+    // moves to mate from white's side, negative when black mates
+    for (fen, mate_in, best_move) in [
+      ("7k/8/8/8/8/8/R7/K5R1 w - - 0 1", 1, "a2h2"),
+      ("k7/r7/8/8/8/8/5PPP/6K1 b - - 0 1", -1, "a7a1"),
+      ("6k1/pp4p1/2p5/2bp4/8/P5Pb/1P3rrP/2BRRN1K b - - 0 1", -2, "g2g1"),
+      ("r1b1kb1r/pppp1ppp/5q2/4n3/3KP3/2N3PN/PPP4P/R1BQ1B1R b kq - 0 1", -3, "f8c5"),
+    ] {
+      let result = search(fen, 7);
+      assert_eq!(result.mate_in, Some(mate_in), "{}", fen);
+      assert_eq!(result.best_move.unwrap().to_uci(), best_move, "{}", fen);
+    }
+  }
+
+  #[test]
+  fn checkmated_root() {
+    let result = search("8/8/8/8/8/5k2/8/5K1q w - - 0 1", 3);
+    assert!(result.best_move.is_none());
+    assert_eq!(result.score, -MATE_SCORE);
+    assert_eq!(result.mate_in, Some(0));
+    let result = search("8/8/8/8/8/5K2/8/5k1Q b - - 0 1", 3);
+    assert_eq!(result.score, MATE_SCORE);
+    assert_eq!(result.mate_in, Some(0));
+  }
+
+  #[test]
+  fn stalemated_root() {
+    let result = search("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1", 3);
+    assert!(result.best_move.is_none());
+    assert_eq!(result.score, 0.0);
+    assert_eq!(result.mate_in, None);
+  }
+
+  #[test]
+  fn threads_agree_with_one_thread_on_few_moves() {
+    // more threads than moves at the root
+    for (fen, best_move) in [
+      ("k7/8/8/8/8/8/1q6/K7 w - - 0 1", "a1b2"),
+      ("6k1/5ppp/8/8/8/8/r7/K3R3 w - - 0 1", "a1a2"),
+    ] {
+      let board = from_forsyth_edwards_notation(fen).unwrap();
+      assert!(board.get_valid_moves().len() <= 2);
+      let game = create_game(board.clone());
+      let limits = SearchLimits{ max_depth: Some(5), ..Default::default() };
+      let mut node = ThoughtNode{ game_state: board, children: Vec::new(), calculated_score: 0.0 };
+      let tree = node.think(&limits, &SearchOptions::default(), &game, &TranspositionTable::new(16), &mut |_| {});
+      let single = search(fen, 5);
+      let parallel = think_parallel(&game, &limits, &SearchOptions::default(), &TranspositionTable::new(16), 4, &mut |_| {});
+      assert_eq!(tree.best_move.unwrap().to_uci(), best_move, "{}", fen);
+      assert_eq!(single.best_move.unwrap().to_uci(), best_move, "{}", fen);
+      assert_eq!(parallel.best_move.unwrap().to_uci(), best_move, "{}", fen);
+      assert_eq!(parallel.depth, 5, "{}", fen);
+    }
+  }
+
+  #[test]
+  fn switches_keep_the_result() {
+    let on = SearchOptions::default();
+    let switched = [
+      on.clone(),
+      SearchOptions{ null_move_pruning: false, ..on.clone() },
+      SearchOptions{ late_move_reductions: false, ..on.clone() },
+      SearchOptions{ futility_pruning: false, ..on.clone() },
+      SearchOptions{ check_extensions: false, ..on.clone() },
+      SearchOptions{ aspiration_windows: false, ..on.clone() },
+    ];
+    for (fen, depth, mate_in, best_move) in [
+      ("7k/8/8/8/8/8/R7/K5R1 w - - 0 1", 7, Some(1), "a2h2"),
+      ("k7/r7/8/8/8/8/5PPP/6K1 b - - 0 1", 7, Some(-1), "a7a1"),
+      ("6k1/pp4p1/2p5/2bp4/8/P5Pb/1P3rrP/2BRRN1K b - - 0 1", 7, Some(-2), "g2g1"),
+      ("r1b1kb1r/pppp1ppp/5q2/4n3/3KP3/2N3PN/PPP4P/R1BQ1B1R b kq - 0 1", 7, Some(-3), "f8c5"),
+      // zugzwang: white wins with the waiting move Kh6, the kind of move a null-move search can miss
+      ("1q1k4/2Rr4/8/2Q3K1/8/8/8/8 w - - 0 1", 9, None, "g5h6"),
+    ] {
+      let game = create_game(from_forsyth_edwards_notation(fen).unwrap());
+      let limits = SearchLimits{ max_depth: Some(depth), ..Default::default() };
+      for options in &switched {
+        let result = think(&game, &limits, options, &TranspositionTable::new(16), &mut |_| {});
+        assert_eq!(result.mate_in, mate_in, "{}", fen);
+        assert_eq!(result.best_move.unwrap().to_uci(), best_move, "{}", fen);
+      }
+    }
+  }
+}
@@ -0,0 +1,58 @@
+use crate::{chess_scoring::TaperedScore, transposition_table::get_table_size};
+
+pub const DEFAULT_PAWN_TABLE_MEGABYTES: usize = 1;
+
+#[derive(Debug, Clone, Copy)]
+struct PawnEntry {
+  key: u64,
+  score: TaperedScore,
+}
+
+// A fixed-size cache of pawn structure scores, indexed by the low bits of the pawn hash. Pawns move far less
+// often than the rest of the pieces, so most positions a search scores have a pawn structure it has scored
+// before. Unlike the transposition table each search thread keeps its own, so there is nothing to lock.
+pub struct PawnTable {
+  entries: Vec<Option<PawnEntry>>,
+  mask: u64,
+  pub probes: u64,
+  pub hits: u64,
+}
+
+impl PawnTable {
+  pub fn new(megabytes: usize) -> PawnTable {
+    let size = get_table_size(megabytes.max(1) * 1024 * 1024, std::mem::size_of::<Option<PawnEntry>>());
+    return PawnTable{
+      entries: vec![None; size as usize],
+      mask: size - 1,
+      probes: 0,
+      hits: 0,
+    };
+  }
+
+  pub fn probe(&mut self, key: u64) -> Option<TaperedScore> {
+    self.probes += 1;
+    let entry = self.entries[(key & self.mask) as usize]?;
+    if entry.key != key {
+      return None;
+    }
+    self.hits += 1;
+    return Some(entry.score);
+  }
+
+  // always replaces, the structure scored last is the one most likely to come up again
+  pub fn store(&mut self, key: u64, score: TaperedScore) {
+    self.entries[(key & self.mask) as usize] = Some(PawnEntry{ key: key, score: score });
+  }
+
+  // forgets every score, for when the parameters they were scored with change
+  pub fn clear(&mut self) {
+    self.entries.fill(None);
+  }
+
+  pub fn get_hit_rate(&self) -> f32 {
+    if self.probes == 0 {
+      return 0.0;
+    }
+    return self.hits as f32 / self.probes as f32;
+  }
+}
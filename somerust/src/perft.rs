@@ -0,0 +1,162 @@
+use crate::{bitboard::{MoveList, Position}, chess_board::ChessBoard, chess_move::ChessMove};
+
+impl ChessBoard {
+  // number of leaf positions reached by playing every legal move sequence of the given length
+  pub fn perft(&self, depth: u32) -> u64 {
+    if depth == 0 {
+      return 1;
+    }
+    return self.clone().perft_in_place(depth);
+  }
+
+  fn perft_in_place(&mut self, depth: u32) -> u64 {
+    let valid_moves = self.get_valid_moves();
+    if depth <= 1 {
+      return valid_moves.len() as u64;
+    }
+    let mut count = 0;
+    for m in valid_moves.iter() {
+      let undo = self.make_move(m);
+      count += self.perft_in_place(depth - 1);
+      self.unmake_move(undo);
+    }
+    return count;
+  }
+
+  // perft split by the first move, for finding which move a generator bug hides under
+  pub fn divide(&self, depth: u32) -> Vec<(ChessMove, u64)> {
+    if depth == 0 {
+      return Vec::new();
+    }
+    let mut board = self.clone();
+    return self.get_valid_moves().into_iter().map(|m| {
+      let undo = board.make_move(&m);
+      let count = if depth == 1 { 1 } else { board.perft_in_place(depth - 1) };
+      board.unmake_move(undo);
+      return (m, count);
+    }).collect();
+  }
+}
+
+// the same count using the bitboard generator, fast enough for deeper checks
+pub fn perft_position(position: &Position, depth: u32) -> u64 {
+  if depth == 0 {
+    return 1;
+  }
+  let mut position = *position;
+  return perft_position_in_place(&mut position, depth);
+}
+
+fn perft_position_in_place(position: &mut Position, depth: u32) -> u64 {
+  let mut moves = MoveList::new();
+  position.generate_legal_moves(&mut moves);
+  if depth <= 1 {
+    return moves.len() as u64;
+  }
+  let mut count = 0;
+  for m in moves.as_slice() {
+    let undo = position.make_move(m);
+    count += perft_position_in_place(position, depth - 1);
+    position.unmake_move(m, undo);
+  }
+  return count;
+}
+
+// checks that unmake_move puts back the exact state make_move started from, for every move in the tree
+pub fn check_unmake_moves(board: &mut ChessBoard, depth: u32) -> Result<(), String> {
+  if depth == 0 {
+    return Ok(());
+  }
+  let fen = board.get_forsyth_edwards_notation();
+  let hash = board.hash;
+  let last_move = board.last_move.clone();
+  for m in board.get_valid_moves().iter() {
+    let undo = board.make_move(m);
+    check_unmake_moves(board, depth - 1)?;
+    board.unmake_move(undo);
+    if board.get_forsyth_edwards_notation() != fen || board.hash != hash || board.last_move != last_move {
+      return Err(format!("unmaking {} gave {} instead of {}", m.to_uci(), board.get_forsyth_edwards_notation(), fen));
+    }
+  }
+  return Ok(());
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::bitboard::from_chess_board;
+  use crate::chess_board::from_forsyth_edwards_notation;
+  use super::{check_unmake_moves, perft_position};
+
+  // published node counts from the chess programming wiki perft results page
+  const START: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+  const KIWIPETE: &str = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+  const POSITION_3: &str = "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1";
+  const POSITION_4: &str = "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1";
+  const POSITION_4_MIRRORED: &str = "r2q1rk1/pP1p2pp/Q4n2/bbp1p3/Np6/1B3NBn/pPPP1PPP/R3K2R b KQ - 0 1";
+  const POSITION_5: &str = "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8";
+  const POSITION_6: &str = "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10";
+
+  fn check(fen: &str, expected: &[u64]) {
+    let board = from_forsyth_edwards_notation(fen).unwrap();
+    for (depth, count) in expected.iter().enumerate() {
+      assert_eq!(board.perft(depth as u32 + 1), *count, "perft({}) of {}", depth + 1, fen);
+    }
+  }
+
+  fn check_bitboard(fen: &str, depth: u32, expected: u64) {
+    let position = from_chess_board(&from_forsyth_edwards_notation(fen).unwrap());
+    assert_eq!(perft_position(&position, depth), expected, "bitboard perft({}) of {}", depth, fen);
+  }
+
+  #[test]
+  fn start_position() {
+    check(START, &[20, 400, 8902, 197281]);
+    check_bitboard(START, 5, 4865609);
+  }
+
+  #[test]
+  fn kiwipete() {
+    check(KIWIPETE, &[48, 2039, 97862]);
+    check_bitboard(KIWIPETE, 4, 4085603);
+  }
+
+  #[test]
+  fn en_passant_and_pins() {
+    check(POSITION_3, &[14, 191, 2812, 43238]);
+    check_bitboard(POSITION_3, 6, 11030083);
+  }
+
+  #[test]
+  fn promotions_and_castling_through_check() {
+    check(POSITION_4, &[6, 264, 9467]);
+    check(POSITION_4_MIRRORED, &[6, 264, 9467]);
+    check_bitboard(POSITION_4, 4, 422333);
+  }
+
+  #[test]
+  fn promotion_with_discovered_check() {
+    check(POSITION_5, &[44, 1486, 62379]);
+    check_bitboard(POSITION_5, 4, 2103487);
+  }
+
+  #[test]
+  fn middlegame() {
+    check(POSITION_6, &[46, 2079, 89890]);
+    check_bitboard(POSITION_6, 4, 3894594);
+  }
+
+  #[test]
+  fn divide_sums_to_perft() {
+    let board = from_forsyth_edwards_notation(KIWIPETE).unwrap();
+    let divided = board.divide(2);
+    assert_eq!(divided.len(), 48);
+    assert_eq!(divided.iter().map(|(_, count)| count).sum::<u64>(), 2039);
+  }
+
+  #[test]
+  fn unmake_restores_state() {
+    check_unmake_moves(&mut from_forsyth_edwards_notation(KIWIPETE).unwrap(), 3).unwrap();
+    check_unmake_moves(&mut from_forsyth_edwards_notation(POSITION_3).unwrap(), 3).unwrap();
+    check_unmake_moves(&mut from_forsyth_edwards_notation("r3k2r/8/8/8/8/8/8/R3K2R b KQkq - 0 1").unwrap(), 2).unwrap();
+  }
+}
@@ -0,0 +1,445 @@
+use std::fmt;
+
+use crate::{chess_board::{ChessBoard, FenError, SanError, create_new_board, from_forsyth_edwards_notation}, chess_color::ChessColor, chess_move::ChessMove};
+
+const SEVEN_TAG_ROSTER: [(&str, &str); 7] = [
+  ("Event", "?"),
+  ("Site", "?"),
+  ("Date", "????.??.??"),
+  ("Round", "?"),
+  ("White", "?"),
+  ("Black", "?"),
+  ("Result", "*"),
+];
+
+const STANDARD_START: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+pub struct PgnGame {
+  // every tag other than Result, FEN and SetUp, which are kept in their own fields
+  pub tags: Vec<(String, String)>,
+  pub starting_position: ChessBoard,
+  pub moves: Vec<ChessMove>,
+  // "1-0", "0-1", "1/2-1/2" or "*"
+  pub result: String,
+}
+
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub enum PgnErrorKind {
+  UnterminatedComment,
+  UnterminatedTag,
+  MalformedTag,
+  UnbalancedVariation,
+  UnexpectedCharacter(char),
+  InvalidFen(FenError),
+  InvalidMove(SanError),
+}
+
+// game is the 1-based index of the game in the file, line the 1-based line the problem was found on
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub struct PgnError {
+  pub game: usize,
+  pub line: usize,
+  pub kind: PgnErrorKind,
+}
+
+impl fmt::Display for PgnError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "game {}, line {}: ", self.game, self.line)?;
+    return match &self.kind {
+      PgnErrorKind::UnterminatedComment => write!(f, "comment is never closed"),
+      PgnErrorKind::UnterminatedTag => write!(f, "tag pair is never closed"),
+      PgnErrorKind::MalformedTag => write!(f, "tag pair is not of the form [Name \"value\"]"),
+      PgnErrorKind::UnbalancedVariation => write!(f, "unbalanced parentheses around a variation"),
+      PgnErrorKind::UnexpectedCharacter(c) => write!(f, "unexpected '{}'", c),
+      PgnErrorKind::InvalidFen(e) => write!(f, "{}", e),
+      PgnErrorKind::InvalidMove(e) => write!(f, "{}", e),
+    };
+  }
+}
+
+impl PgnGame {
+  pub fn get_tag(&self, name: &str) -> Option<&str> {
+    return self.tags.iter().find(|(n, _)| n == name).map(|(_, v)| v.as_str());
+  }
+
+  pub fn set_tag(&mut self, name: &str, value: &str) {
+    match self.tags.iter_mut().find(|(n, _)| n == name) {
+      Some(tag) => tag.1 = String::from(value),
+      None => self.tags.push((String::from(name), String::from(value))),
+    }
+  }
+
+  pub fn get_final_position(&self) -> ChessBoard {
+    let mut board = self.starting_position.clone();
+    for m in self.moves.iter() {
+      board = board.do_move(m);
+    }
+    return board;
+  }
+
+  pub fn get_pgn(&self) -> String {
+    let mut pgn = String::new();
+    for (name, default) in SEVEN_TAG_ROSTER.iter() {
+      let value = if *name == "Result" { self.result.as_str() } else { self.get_tag(name).unwrap_or(default) };
+      pgn.push_str(&format_tag(name, value));
+    }
+    let fen = self.starting_position.get_forsyth_edwards_notation();
+    if fen != STANDARD_START {
+      pgn.push_str(&format_tag("SetUp", "1"));
+      pgn.push_str(&format_tag("FEN", &fen));
+    }
+    for (name, value) in self.tags.iter() {
+      if !SEVEN_TAG_ROSTER.iter().any(|(n, _)| n == name) {
+        pgn.push_str(&format_tag(name, value));
+      }
+    }
+    pgn.push_str("\n");
+
+    let mut tokens: Vec<String> = Vec::new();
+    let mut board = self.starting_position.clone();
+    for (i, m) in self.moves.iter().enumerate() {
+      if board.current_player == ChessColor::White {
+        tokens.push(format!("{}. {}", board.fullmove_number, m.to_san(&board)));
+      } else if i == 0 {
+        tokens.push(format!("{}... {}", board.fullmove_number, m.to_san(&board)));
+      } else {
+        tokens.push(m.to_san(&board));
+      }
+      board = board.do_move(m);
+    }
+    tokens.push(self.result.clone());
+
+    // export format keeps movetext lines under 80 characters
+    let mut line_length = 0;
+    for token in tokens.iter() {
+      if line_length > 0 && line_length + 1 + token.len() > 79 {
+        pgn.push_str("\n");
+        line_length = 0;
+      } else if line_length > 0 {
+        pgn.push_str(" ");
+        line_length += 1;
+      }
+      pgn.push_str(token);
+      line_length += token.len();
+    }
+    pgn.push_str("\n");
+    return pgn;
+  }
+}
+
+pub fn create_pgn_game(starting_position: ChessBoard) -> PgnGame {
+  return PgnGame{
+    tags: Vec::new(),
+    starting_position: starting_position,
+    moves: Vec::new(),
+    result: String::from("*"),
+  };
+}
+
+fn format_tag(name: &str, value: &str) -> String {
+  return format!("[{} \"{}\"]\n", name, value.replace('\\', "\\\\").replace('"', "\\\""));
+}
+
+enum PgnToken {
+  Tag(String, String),
+  Symbol(String),
+  OpenVariation,
+  CloseVariation,
+}
+
+// Splits pgn text into tags, symbols and variation markers, dropping comments, NAGs, move number periods and
+// suffix annotations. Each token carries the line it started on.
+fn tokenize_pgn(text: &str, game: &mut usize) -> Result<Vec<(PgnToken, usize)>, PgnError> {
+  let mut tokens = Vec::new();
+  let chars: Vec<char> = text.chars().collect();
+  let mut line = 1;
+  let mut i = 0;
+  let mut in_movetext = false;
+  while i < chars.len() {
+    let c = chars[i];
+    match c {
+      '\n' => {
+        line += 1;
+        i += 1;
+      },
+      '%' if i == 0 || chars[i - 1] == '\n' => {
+        while i < chars.len() && chars[i] != '\n' {
+          i += 1;
+        }
+      },
+      ';' => {
+        while i < chars.len() && chars[i] != '\n' {
+          i += 1;
+        }
+      },
+      '{' => {
+        let start_line = line;
+        while i < chars.len() && chars[i] != '}' {
+          if chars[i] == '\n' {
+            line += 1;
+          }
+          i += 1;
+        }
+        if i == chars.len() {
+          return Err(PgnError{ game: *game, line: start_line, kind: PgnErrorKind::UnterminatedComment });
+        }
+        i += 1;
+      },
+      '[' => {
+        if in_movetext {
+          *game += 1;
+          in_movetext = false;
+        }
+        let start_line = line;
+        let mut tag = String::new();
+        let mut in_string = false;
+        i += 1;
+        loop {
+          if i == chars.len() || chars[i] == '\n' {
+            return Err(PgnError{ game: *game, line: start_line, kind: PgnErrorKind::UnterminatedTag });
+          }
+          let t = chars[i];
+          i += 1;
+          if in_string && t == '\\' && i < chars.len() {
+            tag.push(chars[i]);
+            i += 1;
+            continue;
+          }
+          if t == '"' {
+            in_string = !in_string;
+          } else if t == ']' && !in_string {
+            break;
+          }
+          tag.push(t);
+        }
+        match parse_tag(&tag) {
+          Some(t) => tokens.push((t, start_line)),
+          None => return Err(PgnError{ game: *game, line: start_line, kind: PgnErrorKind::MalformedTag }),
+        }
+      },
+      '(' => {
+        tokens.push((PgnToken::OpenVariation, line));
+        i += 1;
+      },
+      ')' => {
+        tokens.push((PgnToken::CloseVariation, line));
+        i += 1;
+      },
+      '$' => {
+        i += 1;
+        while i < chars.len() && chars[i].is_ascii_digit() {
+          i += 1;
+        }
+      },
+      '.' | '!' | '?' => {
+        i += 1;
+      },
+      _ if c.is_whitespace() => {
+        i += 1;
+      },
+      _ if c.is_ascii_alphanumeric() || c == '*' => {
+        let mut symbol = String::new();
+        while i < chars.len() && (chars[i].is_ascii_alphanumeric() || "_+#=:-/*".contains(chars[i])) {
+          symbol.push(chars[i]);
+          i += 1;
+        }
+        let is_result = is_result(&symbol);
+        tokens.push((PgnToken::Symbol(symbol), line));
+        in_movetext = !is_result;
+        if is_result {
+          *game += 1;
+        }
+      },
+      _ => return Err(PgnError{ game: *game, line: line, kind: PgnErrorKind::UnexpectedCharacter(c) })
+    }
+  }
+  return Ok(tokens);
+}
+
+fn parse_tag(tag: &str) -> Option<PgnToken> {
+  let tag = tag.trim();
+  let name_end = tag.find(|c: char| c.is_whitespace())?;
+  let name = &tag[..name_end];
+  let value = tag[name_end..].trim();
+  if name.is_empty() || value.len() < 2 || !value.starts_with('"') || !value.ends_with('"') {
+    return None;
+  }
+  return Some(PgnToken::Tag(String::from(name), String::from(&value[1..value.len() - 1])));
+}
+
+fn is_result(symbol: &str) -> bool {
+  return symbol == "1-0" || symbol == "0-1" || symbol == "1/2-1/2" || symbol == "*";
+}
+
+// Reads every game in the pgn text. Only the main line of each game is kept; comments, NAGs and variations are
+// skipped. A game that reaches the end of the file without a result is kept with result "*".
+pub fn read_pgn(text: &str) -> Result<Vec<PgnGame>, PgnError> {
+  // the tokenizer counts games as it goes so its own errors can be reported against the right one
+  let mut tokenizer_game = 1;
+  let tokens = tokenize_pgn(text, &mut tokenizer_game)?;
+
+  let mut games = Vec::new();
+  let mut game_number = 1;
+  let mut tags: Vec<(String, String, usize)> = Vec::new();
+  let mut board: Option<ChessBoard> = None;
+  let mut game: Option<PgnGame> = None;
+  let mut variation_depth = 0;
+
+  for (token, line) in tokens.into_iter() {
+    let error = |kind: PgnErrorKind| -> PgnError {
+      return PgnError{ game: game_number, line: line, kind: kind };
+    };
+    match token {
+      PgnToken::Tag(name, value) => {
+        // a tag after movetext without a result starts the next game
+        if let Some(g) = game.take() {
+          games.push(g);
+          game_number += 1;
+          board = None;
+          variation_depth = 0;
+        }
+        tags.push((name, value, line));
+      },
+      PgnToken::OpenVariation => {
+        variation_depth += 1;
+      },
+      PgnToken::CloseVariation => {
+        if variation_depth == 0 {
+          return Err(error(PgnErrorKind::UnbalancedVariation));
+        }
+        variation_depth -= 1;
+      },
+      PgnToken::Symbol(symbol) => {
+        if variation_depth > 0 {
+          continue;
+        }
+        if game.is_none() {
+          let (g, b) = start_pgn_game(&mut tags, game_number)?;
+          game = Some(g);
+          board = Some(b);
+        }
+        if is_result(&symbol) {
+          let mut g = game.take().unwrap();
+          g.result = symbol;
+          games.push(g);
+          game_number += 1;
+          board = None;
+          continue;
+        }
+        if symbol.chars().all(|c| c.is_ascii_digit()) {
+          continue;
+        }
+        let current = board.take().unwrap();
+        let chess_move = current.parse_san(&symbol).map_err(|e| error(PgnErrorKind::InvalidMove(e)))?;
+        board = Some(current.do_move(&chess_move));
+        game.as_mut().unwrap().moves.push(chess_move);
+      },
+    }
+  }
+  if variation_depth > 0 {
+    return Err(PgnError{ game: game_number, line: text.lines().count(), kind: PgnErrorKind::UnbalancedVariation });
+  }
+  if game.is_none() && !tags.is_empty() {
+    game = Some(start_pgn_game(&mut tags, game_number)?.0);
+  }
+  if let Some(g) = game {
+    games.push(g);
+  }
+  return Ok(games);
+}
+
+fn start_pgn_game(tags: &mut Vec<(String, String, usize)>, game_number: usize) -> Result<(PgnGame, ChessBoard), PgnError> {
+  let starting_position = match tags.iter().find(|(n, _, _)| n == "FEN") {
+    Some((_, fen, line)) => from_forsyth_edwards_notation(fen).map_err(|e| PgnError{ game: game_number, line: *line, kind: PgnErrorKind::InvalidFen(e) })?,
+    None => create_new_board(),
+  };
+  let result = tags.iter().find(|(n, _, _)| n == "Result").map_or(String::from("*"), |(_, v, _)| v.clone());
+  let mut game = create_pgn_game(starting_position.clone());
+  game.tags = tags.drain(..).filter(|(n, _, _)| n != "Result" && n != "FEN" && n != "SetUp").map(|(n, v, _)| (n, v)).collect();
+  game.result = result;
+  return Ok((game, starting_position));
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::chess_board::{SanErrorKind, from_forsyth_edwards_notation};
+  use super::{PgnError, PgnErrorKind, PgnGame, create_pgn_game, read_pgn};
+
+  fn read_error(text: &str) -> PgnError {
+    return read_pgn(text).err().unwrap();
+  }
+
+  fn get_uci_moves(game: &PgnGame) -> Vec<String> {
+    return game.moves.iter().map(|m| m.to_uci()).collect();
+  }
+
+  #[test]
+  fn skips_comments_and_variations() {
+    let text = "[Event \"Test\"]\n[Result \"1-0\"]\n\n1. e4 {the best move, (says) who?} e5 (1... c5 2. Nf3 (2. c3 d5) d6) 2. Nf3! $1 Nc6?! ; a comment to the end of the line (\n3. Bb5 a6 1-0\n";
+    let games = read_pgn(text).unwrap();
+    assert_eq!(games.len(), 1);
+    assert_eq!(get_uci_moves(&games[0]), vec!["e2e4", "e7e5", "g1f3", "b8c6", "f1b5", "a7a6"]);
+    assert_eq!(games[0].result, "1-0");
+    assert_eq!(games[0].get_tag("Event"), Some("Test"));
+    assert_eq!(games[0].get_tag("Result"), None);
+  }
+
+  #[test]
+  fn reads_several_games() {
+    let text = "[White \"A\"]\n\n1. d4 d5 1/2-1/2\n\n1. c4 *\n[White \"C\"]\n1. e4\n[White \"D\"]\n1. f4 0-1";
+    let games = read_pgn(text).unwrap();
+    let summary: Vec<(Option<&str>, usize, &str)> = games.iter().map(|g| (g.get_tag("White"), g.moves.len(), g.result.as_str())).collect();
+    // the third game has no result before the next game's tags
+    assert_eq!(summary, vec![(Some("A"), 2, "1/2-1/2"), (None, 1, "*"), (Some("C"), 1, "*"), (Some("D"), 1, "0-1")]);
+  }
+
+  #[test]
+  fn set_up_positions_round_trip() {
+    let fen = "4k3/8/8/8/8/8/4P3/4K3 b - - 3 20";
+    let text = format!("[SetUp \"1\"]\n[FEN \"{}\"]\n[White \"Say \\\"hi\\\"\"]\n\n20... Kd7 21. e4 Kc6 *\n", fen);
+    let games = read_pgn(&text).unwrap();
+    assert_eq!(games[0].starting_position.get_forsyth_edwards_notation(), fen);
+    assert_eq!(get_uci_moves(&games[0]), vec!["e8d7", "e2e4", "d7c6"]);
+    assert_eq!(games[0].tags, vec![(String::from("White"), String::from("Say \"hi\""))]);
+    let pgn = games[0].get_pgn();
+    assert!(pgn.contains("[SetUp \"1\"]\n[FEN \"4k3/8/8/8/8/8/4P3/4K3 b - - 3 20\"]\n"), "{}", pgn);
+    assert!(pgn.contains("[White \"Say \\\"hi\\\"\"]\n"), "{}", pgn);
+    assert!(pgn.ends_with("\n20... Kd7 21. e4 Kc6 *\n"), "{}", pgn);
+    let again = read_pgn(&pgn).unwrap();
+    assert_eq!(get_uci_moves(&again[0]), get_uci_moves(&games[0]));
+    assert_eq!(again[0].starting_position.get_forsyth_edwards_notation(), fen);
+  }
+
+  #[test]
+  fn standard_start_has_no_set_up() {
+    let mut game = create_pgn_game(from_forsyth_edwards_notation("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1").unwrap());
+    game.moves.push(game.starting_position.parse_san("e4").unwrap());
+    let pgn = game.get_pgn();
+    assert!(!pgn.contains("FEN"));
+    assert!(pgn.starts_with("[Event \"?\"]\n[Site \"?\"]\n[Date \"????.??.??\"]\n[Round \"?\"]\n[White \"?\"]\n[Black \"?\"]\n[Result \"*\"]\n\n1. e4 *\n"), "{}", pgn);
+  }
+
+  #[test]
+  fn errors_name_the_game_and_line() {
+    let error = read_error("1. e4 e5 1-0\n\n[Event \"x\"]\n1. e4 e5\n2. e5 *");
+    assert_eq!((error.game, error.line), (2, 5));
+    assert!(matches!(error.kind, PgnErrorKind::InvalidMove(ref e) if e.kind == SanErrorKind::IllegalMove));
+    assert_eq!(error.to_string(), "game 2, line 5: 'e5' is not a legal move in this position");
+
+    let error = read_error("1. e4 *\n1. d4 {never\nclosed");
+    assert_eq!((error.game, error.line, error.kind), (2, 2, PgnErrorKind::UnterminatedComment));
+    let error = read_error("[Event \"x\"\n1. e4");
+    assert_eq!((error.game, error.line, error.kind), (1, 1, PgnErrorKind::UnterminatedTag));
+    let error = read_error("[Event x]\n1. e4");
+    assert_eq!((error.line, error.kind), (1, PgnErrorKind::MalformedTag));
+    let error = read_error("1. e4 (1. d4\n\n");
+    assert_eq!(error.kind, PgnErrorKind::UnbalancedVariation);
+    let error = read_error("1. e4 e5\n2. Nf3 ) *");
+    assert_eq!((error.line, error.kind), (2, PgnErrorKind::UnbalancedVariation));
+    let error = read_error("1. e4 & e5");
+    assert_eq!(error.kind, PgnErrorKind::UnexpectedCharacter('&'));
+    let error = read_error("[FEN \"8/8/8 w - - 0 1\"]\n\n1. e4 *");
+    assert!(matches!(error.kind, PgnErrorKind::InvalidFen(_)));
+    assert_eq!(error.line, 1);
+  }
+}
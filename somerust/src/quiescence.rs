@@ -0,0 +1,172 @@
+use crate::{bitboard::{BitMove, MAX_MOVES, MoveList, NO_PIECE, Position, WHITE}, chess_scoring::Evaluator, eval_params::EvalParams, move_ordering::{get_capture_order, pick_next_move}, search::{BLACK_WIN_SCORE, SearchOptions, SearchStats, WHITE_WIN_SCORE, get_mated_score}};
+
+// how far past the material won a capture still has to be able to raise the score before delta pruning skips it
+const DELTA_MARGIN: f32 = 2.0;
+
+// Scores a position by playing out captures and promotions until it is quiet, so a leaf in the middle of an
+// exchange isn't scored as if the exchange were over. The side to move can always stand pat on the static
+// score instead of capturing, except in check, where every evasion is searched. Scores are from white's side
+// like the rest of the search. depth counts down the plies left before scoring the position as it stands, and
+// ply is the distance from the root of the whole search.
+pub fn quiescence(position: &mut Position, mut alpha: f32, mut beta: f32, depth: i32, ply: usize, options: &SearchOptions, evaluator: &mut Evaluator, stats: &mut SearchStats) -> f32 {
+  stats.seldepth = stats.seldepth.max(ply as i32);
+  if depth <= 0 {
+    return evaluator.score_position(position);
+  }
+  let is_white = position.side_to_move == WHITE;
+  let in_check = position.is_in_check();
+  let mut moves = MoveList::new();
+  position.generate_legal_moves(&mut moves);
+  if moves.is_empty() {
+    return if in_check { get_mated_score(is_white, ply) } else { 0.0 };
+  }
+  let stand_pat = if in_check { None } else { Some(evaluator.score_position(position)) };
+  let mut best = match stand_pat {
+    Some(score) => score,
+    None => if is_white { BLACK_WIN_SCORE } else { WHITE_WIN_SCORE },
+  };
+  if let Some(score) = stand_pat {
+    if is_white {
+      if score >= beta {
+        return score;
+      }
+      alpha = f32::max(alpha, score);
+    } else {
+      if score <= alpha {
+        return score;
+      }
+      beta = f32::min(beta, score);
+    }
+  }
+
+  let include_checks = options.quiescence_checks && depth == options.quiescence_depth;
+  let mut candidates = MoveList::new();
+  let mut orders = [0; MAX_MOVES];
+  for m in moves.as_slice() {
+    let order = match get_capture_order(m) {
+      Some(order) => order,
+      None if in_check || include_checks => 0,
+      None => continue,
+    };
+    orders[candidates.len()] = order;
+    candidates.push(*m);
+  }
+
+  for i in 0..candidates.len() {
+    pick_next_move(candidates.as_mut_slice(), &mut orders, i);
+    let m = candidates.as_slice()[i];
+    if let Some(score) = stand_pat {
+      if options.delta_pruning && m.is_capture() && !can_reach(score, &m, is_white, alpha, beta, &evaluator.params) {
+        continue;
+      }
+    }
+    let undo = position.make_move(&m);
+    // quiet moves are only here for giving check, unless they are evasions
+    if !m.is_capture() && m.promotion == NO_PIECE && !in_check && !position.is_in_check() {
+      position.unmake_move(&m, undo);
+      continue;
+    }
+    stats.nodes += 1;
+    let score = quiescence(position, alpha, beta, depth - 1, ply + 1, options, evaluator, stats);
+    position.unmake_move(&m, undo);
+    if is_white {
+      best = f32::max(best, score);
+      alpha = f32::max(alpha, best);
+    } else {
+      best = f32::min(best, score);
+      beta = f32::min(beta, best);
+    }
+    if alpha >= beta {
+      break;
+    }
+  }
+  return best;
+}
+
+// whether winning the captured piece, and the promotion if there is one, could bring the score past the bound
+fn can_reach(stand_pat: f32, m: &BitMove, is_white: bool, alpha: f32, beta: f32, params: &EvalParams) -> bool {
+  let mut gain = if m.capture == NO_PIECE { 0.0 } else { params.get_piece_value(m.capture as usize) };
+  if m.promotion != NO_PIECE {
+    gain += params.get_piece_value(m.promotion as usize) - params.get_piece_value(m.piece as usize);
+  }
+  return if is_white {
+    stand_pat + gain + DELTA_MARGIN > alpha
+  } else {
+    stand_pat - gain - DELTA_MARGIN < beta
+  };
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::bitboard::from_chess_board;
+  use crate::chess_board::from_forsyth_edwards_notation;
+  use crate::chess_scoring::{create_evaluator, score_position};
+  use crate::eval_params::EvalParams;
+  use crate::search::{BLACK_WIN_SCORE, MATE_SCORE, SearchOptions, SearchStats, WHITE_WIN_SCORE};
+  use super::quiescence;
+
+  fn search_with(fen: &str, options: &SearchOptions) -> f32 {
+    let mut position = from_chess_board(&from_forsyth_edwards_notation(fen).unwrap());
+    return quiescence(&mut position, BLACK_WIN_SCORE, WHITE_WIN_SCORE, options.quiescence_depth, 0, options, &mut create_evaluator(options.eval_params.clone()), &mut SearchStats::default());
+  }
+
+  fn search(fen: &str) -> f32 {
+    return search_with(fen, &SearchOptions::default());
+  }
+
+  // the static score after playing the moves in coordinate notation
+  fn get_score_after(fen: &str, moves: &[&str]) -> f32 {
+    let mut board = from_forsyth_edwards_notation(fen).unwrap();
+    for uci in moves {
+      let chess_move = board.parse_uci(uci).unwrap();
+      board.make_move(&chess_move);
+    }
+    return score_position(&from_chess_board(&board), &EvalParams::default());
+  }
+
+  #[test]
+  fn recaptures() {
+    // the queen that took on e5 is lost to the pawn on d6, which the static score doesn't see
+    let fen = "k7/8/3p4/4Q3/8/8/8/4K3 b - - 0 1";
+    assert_eq!(search(fen), get_score_after(fen, &["d6e5"]));
+    assert!(search(fen) < get_score_after(fen, &[]));
+  }
+
+  #[test]
+  fn stands_pat() {
+    // taking the defended pawn loses the queen, so white keeps the static score
+    let fen = "4k3/8/3p4/4p3/8/8/8/4QK2 w - - 0 1";
+    assert_eq!(search(fen), get_score_after(fen, &[]));
+    // as does every position once the depth runs out
+    let fen = "k7/8/3p4/4Q3/8/8/8/4K3 b - - 0 1";
+    assert_eq!(search_with(fen, &SearchOptions{ quiescence_depth: 0, ..Default::default() }), get_score_after(fen, &[]));
+  }
+
+  #[test]
+  fn searches_evasions_in_check() {
+    // there is no standing pat in check, every evasion is quiet and the best of them is the score
+    let fen = "4k3/8/8/8/8/8/8/r3K3 w - - 0 1";
+    let best = ["e1d2", "e1e2", "e1f2"].iter().map(|uci| get_score_after(fen, &[uci])).fold(BLACK_WIN_SCORE, f32::max);
+    assert_eq!(search(fen), best);
+  }
+
+  #[test]
+  fn mate_and_stalemate() {
+    assert_eq!(search("7k/6Q1/6K1/8/8/8/8/8 b - - 0 1"), MATE_SCORE);
+    assert_eq!(search("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1"), 0.0);
+    // a capture that mates, a ply from the root
+    assert_eq!(search("4r1k1/5ppp/8/8/8/8/8/K3R3 w - - 0 1"), MATE_SCORE - 1.0);
+  }
+
+  #[test]
+  fn delta_pruning_keeps_quiet_scores() {
+    for fen in [
+      "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+      "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3",
+    ] {
+      let pruned = search_with(fen, &SearchOptions{ delta_pruning: true, ..Default::default() });
+      let unpruned = search_with(fen, &SearchOptions{ delta_pruning: false, ..Default::default() });
+      assert_eq!(pruned, unpruned, "{}", fen);
+    }
+  }
+}
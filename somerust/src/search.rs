@@ -0,0 +1,433 @@
+use std::{fmt, sync::{Arc, atomic::{self, AtomicBool, AtomicU64}}, time::{Duration, Instant}};
+
+use crate::{bitboard::BitMove, chess_color::ChessColor, chess_game::ChessGame, chess_move::ChessMove, chess_scoring::{Evaluator, create_evaluator}, eval_params::{EvalParams, create_default_params}, move_ordering::{MoveOrdering, OrderingStats, create_move_ordering}, transposition_table::{Bound, TranspositionTable}};
+
+// beyond any score the search can return, for the initial window
+pub const BLACK_WIN_SCORE : f32 = -9999999.0;
+pub const WHITE_WIN_SCORE : f32 = 9999999.0;
+
+// Checkmate is scored MATE_SCORE less the ply it happens at, positive when white mates, so a quicker mate
+// always scores better than a slower one. Anything within MAX_MATE_PLY of MATE_SCORE is a mate.
+pub const MATE_SCORE: f32 = 1000000.0;
+const MAX_MATE_PLY: f32 = 1000.0;
+
+// deepest iteration tried when no depth limit is given
+pub const MAX_SEARCH_DEPTH: i32 = 64;
+
+// Limits on a single search; the search stops at whichever is reached first. With none set it runs to
+// MAX_SEARCH_DEPTH.
+#[derive(Debug, Clone, Default)]
+pub struct SearchLimits {
+  pub max_depth: Option<i32>,
+  pub movetime: Option<Duration>,
+  pub nodes: Option<u64>,
+  pub deadline: Option<Instant>,
+}
+
+impl SearchLimits {
+  pub fn get_max_depth(&self) -> i32 {
+    return self.max_depth.unwrap_or(MAX_SEARCH_DEPTH).min(MAX_SEARCH_DEPTH);
+  }
+
+  // the earlier of the deadline and the end of the movetime for a search begun at start
+  pub fn get_stop_instant(&self, start: Instant) -> Option<Instant> {
+    let movetime_end = self.movetime.map(|m| start + m);
+    return match (movetime_end, self.deadline) {
+      (Some(a), Some(b)) => Some(a.min(b)),
+      (a, b) => a.or(b),
+    };
+  }
+
+  pub fn is_exhausted(&self, start: Instant, nodes: u64) -> bool {
+    if self.nodes.map_or(false, |n| nodes >= n) {
+      return true;
+    }
+    return self.get_stop_instant(start).map_or(false, |stop| Instant::now() >= stop);
+  }
+}
+
+#[derive(Debug, Clone)]
+pub struct SearchResult {
+  // none when the position has no legal moves
+  pub best_move: Option<ChessMove>,
+  pub score: f32,
+  // see get_mate_in
+  pub mate_in: Option<i32>,
+  // the line the engine expects, starting with best_move
+  pub pv: Vec<ChessMove>,
+  // the last iteration that completed, which is the one the move and score come from
+  pub depth: i32,
+  pub seldepth: i32,
+  pub nodes: u64,
+  pub elapsed: Duration,
+  pub ordering: OrderingStats,
+}
+
+// the score of the side to move being checkmated at ply
+pub fn get_mated_score(is_white: bool, ply: usize) -> f32 {
+  let score = MATE_SCORE - ply as f32;
+  return if is_white { -score } else { score };
+}
+
+pub fn is_mate_score(score: f32) -> bool {
+  return score.abs() >= MATE_SCORE - MAX_MATE_PLY;
+}
+
+// Moves until mate for a score from a search, positive when white mates and negative when black does.
+pub fn get_mate_in(score: f32) -> Option<i32> {
+  if !is_mate_score(score) {
+    return None;
+  }
+  let plies = (MATE_SCORE - score.abs()) as i32;
+  let moves = (plies + 1) / 2;
+  return Some(if score > 0.0 { moves } else { -moves });
+}
+
+// The table keeps mate scores as the distance from the stored position rather than from the root, since the
+// same position can come up at different plies.
+pub fn score_to_table(score: f32, ply: usize) -> f32 {
+  if !is_mate_score(score) {
+    return score;
+  }
+  return if score > 0.0 { score + ply as f32 } else { score - ply as f32 };
+}
+
+pub fn score_from_table(score: f32, ply: usize) -> f32 {
+  if !is_mate_score(score) {
+    return score;
+  }
+  return if score > 0.0 { score - ply as f32 } else { score + ply as f32 };
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SearchStats {
+  pub nodes: u64,
+  // deepest ply from the root reached, counting quiescence
+  pub seldepth: i32,
+}
+
+// What a search is asked to do, handed as one to each thread of a parallel search.
+pub struct SearchRequest<'a> {
+  pub game: &'a ChessGame,
+  pub limits: &'a SearchLimits,
+  pub options: &'a SearchOptions,
+  pub table: &'a TranspositionTable,
+}
+
+// What the threads of a parallel search share besides the transposition table.
+#[derive(Debug, Default)]
+pub struct SharedSearch {
+  // set when the main thread is done, to stop the helpers, or from outside to stop the search early; it is
+  // only seen once a thread has completed an iteration, so there is always a move
+  pub stop: AtomicBool,
+  pub nodes: AtomicU64,
+}
+
+// What the engine reports after each iteration while it is thinking.
+#[derive(Debug, Clone)]
+pub struct SearchInfo {
+  pub depth: i32,
+  pub seldepth: i32,
+  pub score: f32,
+  pub mate_in: Option<i32>,
+  pub nodes: u64,
+  pub nodes_per_second: u64,
+  // permille of the transposition table in use
+  pub hash_fill: u32,
+  pub elapsed: Duration,
+  pub pv: Vec<ChessMove>,
+}
+
+// in the style of a UCI info line, with the score in pawns or moves to mate from white's side and the line in
+// coordinate notation
+impl fmt::Display for SearchInfo {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    let pv: Vec<String> = self.pv.iter().map(|m| m.to_uci()).collect();
+    let score = match self.mate_in {
+      Some(moves) => format!("mate {}", moves),
+      None => format!("{:.2}", self.score),
+    };
+    return write!(f, "depth {} seldepth {} score {} nodes {} nps {} hashfull {} time {} pv {}",
+      self.depth, self.seldepth, score, self.nodes, self.nodes_per_second, self.hash_fill, self.elapsed.as_millis(), pv.join(" "));
+  }
+}
+
+// Switches for the parts of the search that trade accuracy for speed, so their effect can be measured.
+#[derive(Debug, Clone)]
+pub struct SearchOptions {
+  // plies of captures and promotions searched past the nominal depth, 0 scores leaves as they stand
+  pub quiescence_depth: i32,
+  // also search quiet moves that give check on the first ply of quiescence
+  pub quiescence_checks: bool,
+  // skip captures in quiescence that can't bring the score back up to alpha even with a margin
+  pub delta_pruning: bool,
+  // The rest only apply to the negamax search; ThoughtNode always searches the full tree to the given depth.
+  // give the opponent a free move at reduced depth, and cut off if they still can't get back under beta
+  pub null_move_pruning: bool,
+  // search quiet moves late in the order to less depth, and again at full depth only if they beat alpha
+  pub late_move_reductions: bool,
+  // skip quiet moves a ply or two from the leaves when the static score is too far below alpha to catch up
+  pub futility_pruning: bool,
+  // search a ply deeper when in check, so checks near the leaves aren't cut off before they are answered
+  pub check_extensions: bool,
+  // search the root with a window around the last iteration's score, widening it when the score falls outside
+  pub aspiration_windows: bool,
+  // the weights positions are scored with
+  pub eval_params: Arc<EvalParams>,
+}
+
+impl Default for SearchOptions {
+  fn default() -> SearchOptions {
+    return SearchOptions{
+      quiescence_depth: 8,
+      quiescence_checks: false,
+      delta_pruning: true,
+      null_move_pruning: true,
+      late_move_reductions: true,
+      futility_pruning: true,
+      check_extensions: true,
+      aspiration_windows: true,
+      eval_params: create_default_params(),
+    };
+  }
+}
+
+// state shared by every node of one search
+pub struct SearchContext<'a> {
+  // keys of the positions leading up to the node being searched
+  pub history: Vec<u64>,
+  // length of history at the root, so the ply of a node is how far history has grown past it
+  pub root_history_len: usize,
+  pub table: &'a TranspositionTable,
+  pub ordering: MoveOrdering,
+  pub limits: SearchLimits,
+  pub options: SearchOptions,
+  pub evaluator: Evaluator,
+  pub stop_instant: Option<Instant>,
+  pub stats: SearchStats,
+  // the best line found from each ply of the current line, pv[0] being the principal variation
+  pub pv: Vec<Vec<BitMove>>,
+  // the other threads of a parallel search, if there are any
+  pub shared: Option<&'a SharedSearch>,
+  // how many of this thread's nodes have been added to the shared count
+  pub shared_nodes_reported: u64,
+  // the clock and the other threads are only checked every so often, both are slow compared to searching a node
+  pub next_check: u64,
+  // once a limit is reached every node returns straight away and nothing more is stored
+  pub stopped: bool,
+  // false until the first iteration completes, so there is always a move to play
+  pub can_stop: bool,
+}
+
+impl<'a> SearchContext<'a> {
+  pub fn get_ply(&self) -> usize {
+    return self.history.len() - self.root_history_len;
+  }
+
+  // any repetition inside the search is scored as a draw, since whoever can repeat once can repeat again
+  pub fn is_repetition(&self, hash: u64) -> bool {
+    return self.history.contains(&hash);
+  }
+
+  // a node starts with no line of its own, whether it is searched or not
+  pub fn clear_pv(&mut self, ply: usize) {
+    if self.pv.len() <= ply + 1 {
+      self.pv.resize(ply + 2, Vec::new());
+    }
+    self.pv[ply].clear();
+  }
+
+  // the move at ply followed by the line found below it
+  pub fn update_pv(&mut self, ply: usize, m: &BitMove) {
+    let (line, rest) = self.pv.split_at_mut(ply + 1);
+    line[ply].clear();
+    line[ply].push(*m);
+    line[ply].extend_from_slice(&rest[0]);
+  }
+
+  // nodes searched by every thread so far
+  pub fn get_total_nodes(&self) -> u64 {
+    return match self.shared {
+      Some(shared) => shared.nodes.load(atomic::Ordering::Relaxed) + self.stats.nodes - self.shared_nodes_reported,
+      None => self.stats.nodes,
+    };
+  }
+
+  pub fn report_nodes(&mut self) {
+    if let Some(shared) = self.shared {
+      shared.nodes.fetch_add(self.stats.nodes - self.shared_nodes_reported, atomic::Ordering::Relaxed);
+      self.shared_nodes_reported = self.stats.nodes;
+    }
+  }
+
+  pub fn count_node(&mut self) {
+    self.stats.nodes += 1;
+    if self.can_stop && self.limits.nodes.map_or(false, |n| self.get_total_nodes() >= n) {
+      self.stopped = true;
+    }
+    if self.stats.nodes >= self.next_check {
+      self.next_check = self.stats.nodes + 1024;
+      self.report_nodes();
+      if self.can_stop && self.shared.map_or(false, |shared| shared.stop.load(atomic::Ordering::Relaxed)) {
+        self.stopped = true;
+      }
+      if self.can_stop && self.stop_instant.map_or(false, |stop| Instant::now() >= stop) {
+        self.stopped = true;
+      }
+    }
+  }
+}
+
+pub fn create_context(history: Vec<u64>, table: &TranspositionTable, limits: SearchLimits, options: SearchOptions, start: Instant) -> SearchContext<'_> {
+  return SearchContext{
+    root_history_len: history.len(),
+    history: history,
+    table: table,
+    ordering: create_move_ordering(),
+    evaluator: create_evaluator(options.eval_params.clone()),
+    stop_instant: limits.get_stop_instant(start),
+    limits: limits,
+    options: options,
+    stats: SearchStats::default(),
+    pv: Vec::new(),
+    shared: None,
+    shared_nodes_reported: 0,
+    next_check: 0,
+    stopped: false,
+    can_stop: false,
+  };
+}
+
+// Searches the root of an iteration to the depth given, returning its score from white's side and best move,
+// or none if the root has no moves.
+pub type RootSearch<'a> = dyn FnMut(&mut SearchContext, i32) -> Option<(f32, ChessMove)> + 'a;
+
+// The iterative deepening both searches share: searches one ply deeper at a time, starting at first_depth so
+// the threads of a parallel search are spread over different depths, until a limit is reached, and returns the
+// best move of the deepest iteration that finished. on_info is called after every completed iteration, and the
+// result's nodes are those of all threads.
+pub fn think_iteratively(request: &SearchRequest, shared: Option<&SharedSearch>, first_depth: i32, on_info: &mut dyn FnMut(&SearchInfo), search_root: &mut RootSearch) -> SearchResult {
+  let start = Instant::now();
+  let limits = request.limits;
+  let game = request.game;
+  let mut context = create_context(get_game_history(game), request.table, limits.clone(), request.options.clone(), start);
+  context.shared = shared;
+  let mut result = SearchResult{
+    best_move: None,
+    score: 0.0,
+    mate_in: None,
+    pv: Vec::new(),
+    depth: 0,
+    seldepth: 0,
+    nodes: 0,
+    elapsed: Duration::default(),
+    ordering: OrderingStats::default(),
+  };
+  // a root without moves has no line for an iteration to report, so a checkmate is scored here; a stalemate
+  // is left at the draw score
+  if game.board.get_valid_moves().is_empty() && game.board.is_in_check() {
+    result.score = get_mated_score(game.board.current_player == ChessColor::White, 0);
+    result.mate_in = get_mate_in(result.score);
+  }
+  for depth in first_depth.max(1)..=limits.get_max_depth() {
+    let (score, best_move) = match search_root(&mut context, depth) {
+      Some(root) if !context.stopped => root,
+      _ => break,
+    };
+    result.best_move = Some(best_move);
+    result.score = score;
+    result.mate_in = get_mate_in(score);
+    result.pv = context.pv[0].iter().map(|m| m.to_chess_move()).collect();
+    result.depth = depth;
+    result.seldepth = context.stats.seldepth;
+    context.can_stop = true;
+    let elapsed = Instant::now() - start;
+    let nodes = context.get_total_nodes();
+    on_info(&SearchInfo{
+      depth: depth,
+      seldepth: context.stats.seldepth,
+      score: score,
+      mate_in: result.mate_in,
+      nodes: nodes,
+      nodes_per_second: (nodes as f64 / elapsed.as_secs_f64().max(0.001)) as u64,
+      hash_fill: request.table.get_fill(),
+      elapsed: elapsed,
+      pv: result.pv.clone(),
+    });
+    if limits.is_exhausted(start, context.get_total_nodes()) {
+      break;
+    }
+  }
+  context.report_nodes();
+  result.nodes = context.get_total_nodes();
+  result.ordering = context.ordering.stats;
+  result.elapsed = Instant::now() - start;
+  return result;
+}
+
+// keys of the positions before the current one that it could still repeat
+pub fn get_game_history(game: &ChessGame) -> Vec<u64> {
+  let reversible_keys = game.get_reversible_keys();
+  return reversible_keys[..reversible_keys.len() - 1].to_vec();
+}
+
+// what a score returned for the window alpha to beta says about the true value
+pub fn get_bound(score: f32, alpha: f32, beta: f32) -> Bound {
+  return if score <= alpha {
+    Bound::Upper
+  } else if score >= beta {
+    Bound::Lower
+  } else {
+    Bound::Exact
+  };
+}
+
+#[cfg(test)]
+mod tests {
+  use std::time::{Duration, Instant};
+  use crate::chess_board::create_new_board;
+  use super::{MATE_SCORE, MAX_SEARCH_DEPTH, SearchInfo, SearchLimits};
+
+  #[test]
+  fn stop_instant_is_the_earlier_limit() {
+    let start = Instant::now();
+    let limits = SearchLimits{ movetime: Some(Duration::from_millis(50)), deadline: Some(start + Duration::from_millis(100)), ..Default::default() };
+    assert_eq!(limits.get_stop_instant(start), Some(start + Duration::from_millis(50)));
+    let limits = SearchLimits{ movetime: Some(Duration::from_millis(50)), deadline: Some(start + Duration::from_millis(20)), ..Default::default() };
+    assert_eq!(limits.get_stop_instant(start), Some(start + Duration::from_millis(20)));
+    let limits = SearchLimits{ movetime: Some(Duration::from_millis(50)), ..Default::default() };
+    assert_eq!(limits.get_stop_instant(start), Some(start + Duration::from_millis(50)));
+    assert_eq!(SearchLimits::default().get_stop_instant(start), None);
+  }
+
+  #[test]
+  fn limits() {
+    let start = Instant::now();
+    let limits = SearchLimits{ nodes: Some(100), ..Default::default() };
+    assert!(!limits.is_exhausted(start, 99));
+    assert!(limits.is_exhausted(start, 100));
+    assert!(!SearchLimits::default().is_exhausted(start, u64::MAX));
+    assert_eq!(SearchLimits::default().get_max_depth(), MAX_SEARCH_DEPTH);
+    assert_eq!(SearchLimits{ max_depth: Some(1000), ..Default::default() }.get_max_depth(), MAX_SEARCH_DEPTH);
+  }
+
+  #[test]
+  fn info_lines() {
+    let board = create_new_board();
+    let info = SearchInfo{
+      depth: 5,
+      seldepth: 9,
+      score: 0.35,
+      nodes: 12345,
+      nodes_per_second: 100000,
+      hash_fill: 12,
+      elapsed: Duration::from_millis(123),
+      mate_in: None,
+      pv: vec![board.parse_uci("e2e4").unwrap(), board.parse_uci("g1f3").unwrap()],
+    };
+    assert_eq!(info.to_string(), "depth 5 seldepth 9 score 0.35 nodes 12345 nps 100000 hashfull 12 time 123 pv e2e4 g1f3");
+    // mates are given in moves rather than pawns, negative when black mates
+    let info = SearchInfo{ score: -(MATE_SCORE - 3.0), mate_in: Some(-2), ..info };
+    assert_eq!(info.to_string(), "depth 5 seldepth 9 score mate -2 nodes 12345 nps 100000 hashfull 12 time 123 pv e2e4 g1f3");
+  }
+}
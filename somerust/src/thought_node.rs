@@ -1,10 +1,6 @@
-use std::cmp::{Ordering};
+use std::{cmp::{Ordering}, time::Instant};
 
-use crate::{chess_board::ChessBoard, chess_color::ChessColor, chess_move::{ChessMove, get_check}, chess_scoring::score_game_state};
-
-
-const BLACK_WIN_SCORE : f32 = -9999999.0;
-const WHITE_WIN_SCORE : f32 = 9999999.0;
+use crate::{bitboard::{BitMove, from_chess_board, from_chess_move}, chess_board::ChessBoard, chess_color::ChessColor, chess_game::ChessGame, chess_move::ChessMove, quiescence::quiescence, search::{BLACK_WIN_SCORE, MATE_SCORE, SearchContext, SearchInfo, SharedSearch, SearchLimits, SearchOptions, SearchRequest, SearchResult, WHITE_WIN_SCORE, create_context, get_bound, get_game_history, get_mated_score, score_from_table, score_to_table, think_iteratively}, transposition_table::{Bound, DEFAULT_TABLE_MEGABYTES, TableEntry, TranspositionTable, get_move_code}};
 
 pub struct ThoughtNode {
   pub game_state: ChessBoard,
@@ -14,27 +10,64 @@ pub struct ThoughtNode {
 
 impl ThoughtNode {
   pub fn alphabeta(&mut self, depth: i32) -> f32 {
-    return self.alpha_beta_pruning(depth, BLACK_WIN_SCORE, WHITE_WIN_SCORE);
+    return self.alphabeta_with_table(depth, &TranspositionTable::new(DEFAULT_TABLE_MEGABYTES));
+  }
+
+  // the table can be shared between searches so results carry over to the next move
+  pub fn alphabeta_with_table(&mut self, depth: i32, table: &TranspositionTable) -> f32 {
+    let mut context = create_context(Vec::new(), table, SearchLimits::default(), SearchOptions::default(), Instant::now());
+    let entry = table.probe(self.game_state.hash);
+    return self.alpha_beta_pruning(depth, BLACK_WIN_SCORE, WHITE_WIN_SCORE, &mut context, entry);
+  }
+
+  // searches the current position of the game, scoring lines that repeat an earlier position of it as draws
+  pub fn alphabeta_in_game(&mut self, depth: i32, game: &ChessGame, table: &TranspositionTable) -> f32 {
+    let mut context = create_context(get_game_history(game), table, SearchLimits::default(), SearchOptions::default(), Instant::now());
+    let entry = table.probe(self.game_state.hash);
+    return self.alpha_beta_pruning(depth, BLACK_WIN_SCORE, WHITE_WIN_SCORE, &mut context, entry);
+  }
+
+  // Iterative deepening with search::think_iteratively. get_best_move leaves the children sorted so each
+  // iteration starts with the previous best move, and the table brings the rest of the previous best line
+  // forward.
+  pub fn think(&mut self, limits: &SearchLimits, options: &SearchOptions, game: &ChessGame, table: &TranspositionTable, on_info: &mut dyn FnMut(&SearchInfo)) -> SearchResult {
+    let request = SearchRequest{ game: game, limits: limits, options: options, table: table };
+    return self.think_shared(&request, on_info, None, 1);
+  }
+
+  // think as one of the threads of a parallel search
+  pub fn think_shared(&mut self, request: &SearchRequest, on_info: &mut dyn FnMut(&SearchInfo), shared: Option<&SharedSearch>, first_depth: i32) -> SearchResult {
+    return think_iteratively(request, shared, first_depth, on_info, &mut |context, depth| {
+      let entry = request.table.probe(self.game_state.hash);
+      let score = self.alpha_beta_pruning(depth, BLACK_WIN_SCORE, WHITE_WIN_SCORE, context, entry);
+      if self.children.is_empty() {
+        return None;
+      }
+      return Some((score, self.get_best_move()));
+    });
   }
 
-  fn alpha_beta_pruning(&mut self, depth: i32, mut alpha: f32, mut beta: f32) -> f32 {
+  // entry is what the table had for this node
+  fn alpha_beta_pruning(&mut self, depth: i32, mut alpha: f32, mut beta: f32, context: &mut SearchContext, entry: Option<TableEntry>) -> f32 {
+    context.count_node();
+    if context.stopped {
+      return 0.0;
+    }
+    let ply = context.get_ply();
+    context.clear_pv(ply);
+    context.stats.seldepth = context.stats.seldepth.max(ply as i32);
     if depth == 0 {
-      self.calculated_score = score_game_state(&self.game_state);
+      let quiescence_depth = context.options.quiescence_depth;
+      self.calculated_score = quiescence(&mut from_chess_board(&self.game_state), alpha, beta, quiescence_depth, ply, &context.options, &mut context.evaluator, &mut context.stats);
+      context.table.store(self.game_state.hash, TableEntry{ depth: 0, bound: get_bound(self.calculated_score, alpha, beta), score: score_to_table(self.calculated_score, ply), best_move: 0 });
       return self.calculated_score;
     }
     if self.children.is_empty() {
       let valid_moves = self.game_state.get_valid_moves();
       if valid_moves.is_empty() {
-        let check = get_check(&self.game_state.squares, &ChessColor::White).or(get_check(&self.game_state.squares, &ChessColor::Black));
-        return match check {
-          Some(c) => {
-            match c {
-              ChessColor::Black => WHITE_WIN_SCORE,
-              ChessColor::White => BLACK_WIN_SCORE
-            }
-          }
-          None => 0.0
-        }
+        let is_white = self.game_state.current_player == ChessColor::White;
+        self.calculated_score = if self.game_state.is_in_check() { get_mated_score(is_white, ply) } else { 0.0 };
+        return self.calculated_score;
       }
       for chess_move in valid_moves.iter() {
         self.children.push(ThoughtNode{
@@ -44,28 +77,107 @@ impl ThoughtNode {
         });
       }
     }
+    let ordering = &context.ordering;
+    self.children.sort_by_cached_key(|c| -ordering.get_move_order(&c.get_last_move(), ply));
+    // the move that was best last time this position was searched goes first, as it is the most likely to cut off
+    if let Some(e) = entry {
+      if let Some(i) = self.children.iter().position(|c| e.is_best_move(&c.get_last_move())) {
+        self.children[..=i].rotate_right(1);
+      }
+    }
+    // mate-distance pruning: no line from here can end sooner than mate at this ply, so when a mate that quick
+    // is already outside the window there is nothing to search for
+    if ply > 0 {
+      alpha = f32::max(alpha, -(MATE_SCORE - ply as f32));
+      beta = f32::min(beta, MATE_SCORE - ply as f32);
+      if alpha >= beta {
+        self.calculated_score = alpha;
+        return self.calculated_score;
+      }
+    }
+    let original_alpha = alpha;
+    let original_beta = beta;
+    let mut best_child = 0;
+    context.history.push(self.game_state.hash);
     if self.game_state.current_player == ChessColor::White {
       self.calculated_score = BLACK_WIN_SCORE;
-      for node in self.children.iter_mut() {
-        self.calculated_score = f32::max(self.calculated_score, node.alpha_beta_pruning(depth - 1, alpha, beta));
+      for (i, node) in self.children.iter_mut().enumerate() {
+        let score = node.search_child(depth - 1, alpha, beta, context);
+        if score > alpha || i == 0 {
+          context.update_pv(ply, &node.get_last_move());
+        }
+        if score > self.calculated_score || i == 0 {
+          self.calculated_score = score;
+          best_child = i;
+        }
         alpha = f32::max(alpha, self.calculated_score);
-        if alpha > beta {
+        if alpha > beta && !context.stopped {
+          context.ordering.record_cutoff(&node.get_last_move(), ply, depth, i);
+        }
+        if alpha > beta || context.stopped {
           break;
         }
       }
     } else {
       self.calculated_score = WHITE_WIN_SCORE;
-      for node in self.children.iter_mut() {
-        self.calculated_score = f32::min(self.calculated_score, node.alpha_beta_pruning(depth - 1, alpha, beta));
+      for (i, node) in self.children.iter_mut().enumerate() {
+        let score = node.search_child(depth - 1, alpha, beta, context);
+        if score < beta || i == 0 {
+          context.update_pv(ply, &node.get_last_move());
+        }
+        if score < self.calculated_score || i == 0 {
+          self.calculated_score = score;
+          best_child = i;
+        }
         beta = f32::min(beta, self.calculated_score);
-        if beta < alpha {
+        if beta < alpha && !context.stopped {
+          context.ordering.record_cutoff(&node.get_last_move(), ply, depth, i);
+        }
+        if beta < alpha || context.stopped {
           break;
         }
       }
     }
+    context.history.pop();
+    if context.stopped {
+      return self.calculated_score;
+    }
+    let bound = get_bound(self.calculated_score, original_alpha, original_beta);
+    let best_move = get_move_code(&self.children[best_child].get_last_move());
+    context.table.store(self.game_state.hash, TableEntry{ depth: depth, bound: bound, score: score_to_table(self.calculated_score, ply), best_move: best_move });
     return self.calculated_score;
   }
 
+  fn search_child(&mut self, depth: i32, alpha: f32, beta: f32, context: &mut SearchContext) -> f32 {
+    context.clear_pv(context.get_ply());
+    if self.is_draw_by_rule(context) {
+      self.calculated_score = 0.0;
+      return self.calculated_score;
+    }
+    let entry = context.table.probe(self.game_state.hash);
+    if let Some(e) = entry {
+      let score = score_from_table(e.score, context.get_ply());
+      let usable = match e.bound {
+        Bound::Exact => true,
+        Bound::Lower => score >= beta,
+        Bound::Upper => score <= alpha,
+      };
+      if e.depth >= depth && usable {
+        context.table.record_cutoff();
+        self.calculated_score = score;
+        return self.calculated_score;
+      }
+    }
+    return self.alpha_beta_pruning(depth, alpha, beta, context, entry);
+  }
+
+  fn is_draw_by_rule(&self, context: &SearchContext) -> bool {
+    if self.game_state.halfmove_clock >= 100 || self.game_state.has_insufficient_material() {
+      return true;
+    }
+    return context.is_repetition(self.game_state.hash);
+  }
+
   pub fn get_best_move(&mut self) -> ChessMove {
     if self.children.is_empty() {
       panic!("haven't thought yet");
@@ -84,4 +196,84 @@ impl ThoughtNode {
       }
     };
   }
-}
\ No newline at end of file
+
+  fn get_last_move(&self) -> BitMove {
+    return from_chess_move(self.game_state.last_move.as_ref().unwrap());
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::time::Duration;
+  use crate::chess_board::{ChessBoard, create_new_board, from_forsyth_edwards_notation};
+  use crate::chess_game::create_game;
+  use crate::search::{MATE_SCORE, SearchInfo, SearchLimits, SearchOptions, SearchResult};
+  use crate::transposition_table::TranspositionTable;
+  use super::ThoughtNode;
+
+  // the result and what was reported along the way
+  fn search_with(board: ChessBoard, limits: &SearchLimits) -> (SearchResult, Vec<SearchInfo>) {
+    let mut node = ThoughtNode{ game_state: board.clone(), children: Vec::new(), calculated_score: 0.0 };
+    let mut infos = Vec::new();
+    let result = node.think(limits, &SearchOptions::default(), &create_game(board), &TranspositionTable::new(16), &mut |info| infos.push(info.clone()));
+    return (result, infos);
+  }
+
+  fn search(fen: &str, depth: i32) -> SearchResult {
+    let limits = SearchLimits{ max_depth: Some(depth), ..Default::default() };
+    return search_with(from_forsyth_edwards_notation(fen).unwrap(), &limits).0;
+  }
+
+  #[test]
+  fn limits_stop_early() {
+    // either limit ends the search long before the depth limit, with the move of a finished iteration
+    for limits in [
+      SearchLimits{ max_depth: Some(30), nodes: Some(20_000), ..Default::default() },
+      SearchLimits{ max_depth: Some(30), movetime: Some(Duration::from_millis(50)), ..Default::default() },
+    ] {
+      let board = create_new_board();
+      let (result, infos) = search_with(board.clone(), &limits);
+      assert!(result.depth >= 1 && result.depth < 30, "{:?}", limits);
+      assert!(board.get_valid_moves().contains(result.best_move.as_ref().unwrap()), "{:?}", limits);
+      let last = infos.last().unwrap();
+      assert_eq!((last.depth, &last.pv[0]), (result.depth, result.best_move.as_ref().unwrap()), "{:?}", limits);
+    }
+  }
+
+  #[test]
+  fn reports_each_iteration() {
+    let (result, infos) = search_with(create_new_board(), &SearchLimits{ max_depth: Some(4), ..Default::default() });
+    assert_eq!(infos.iter().map(|info| info.depth).collect::<Vec<i32>>(), vec![1, 2, 3, 4]);
+    for info in infos.iter() {
+      assert!(info.seldepth >= info.depth);
+      assert!(!info.pv.is_empty());
+    }
+    let last = infos.last().unwrap();
+    assert_eq!(&last.pv[0], result.best_move.as_ref().unwrap());
+    assert_eq!(last.pv, result.pv);
+    assert_eq!(last.score, result.score);
+  }
+
+
+  #[test]
+  fn mate_in_n() {
+    assert_eq!(search("7k/8/8/8/8/8/R7/K5R1 w - - 0 1", 3).mate_in, Some(1));
+    assert_eq!(search("k7/r7/8/8/8/8/5PPP/6K1 b - - 0 1", 3).mate_in, Some(-1));
+    assert_eq!(search("6k1/pp4p1/2p5/2bp4/8/P5Pb/1P3rrP/2BRRN1K b - - 0 1", 4).mate_in, Some(-2));
+    // and reported in moves once found
+    let (_, infos) = search_with(from_forsyth_edwards_notation("7k/8/8/8/8/8/R7/K5R1 w - - 0 1").unwrap(), &SearchLimits{ max_depth: Some(3), ..Default::default() });
+    assert!(infos.last().unwrap().to_string().contains(" score mate 1 "));
+  }
+
+  #[test]
+  fn checkmated_and_stalemated_roots() {
+    let result = search("8/8/8/8/8/5k2/8/5K1q w - - 0 1", 2);
+    assert!(result.best_move.is_none());
+    assert_eq!(result.score, -MATE_SCORE);
+    assert_eq!(result.mate_in, Some(0));
+    let result = search("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1", 2);
+    assert!(result.best_move.is_none());
+    assert_eq!(result.score, 0.0);
+    assert_eq!(result.mate_in, None);
+  }
+}
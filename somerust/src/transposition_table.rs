@@ -0,0 +1,314 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::bitboard::{BISHOP, BitMove, KNIGHT, NO_PIECE, QUEEN, ROOK};
+
+pub const DEFAULT_TABLE_MEGABYTES: usize = 16;
+
+#[derive(Eq, PartialEq, Debug, Clone, Copy)]
+pub enum Bound {
+  // the score is the value of the position
+  Exact,
+  // the search failed high, the value is at least the score
+  Lower,
+  // the search failed low, the value is at most the score
+  Upper,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct TableEntry {
+  pub depth: i32,
+  pub bound: Bound,
+  pub score: f32,
+  // see get_move_code, 0 when the search had no best move
+  pub best_move: u16,
+}
+
+impl TableEntry {
+  pub fn is_best_move(&self, m: &BitMove) -> bool {
+    return self.best_move != 0 && self.best_move == get_move_code(m);
+  }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct TableStats {
+  pub probes: u64,
+  pub hits: u64,
+  pub cutoffs: u64,
+  pub stores: u64,
+}
+
+impl TableStats {
+  pub fn get_hit_rate(&self) -> f32 {
+    if self.probes == 0 {
+      return 0.0;
+    }
+    return self.hits as f32 / self.probes as f32;
+  }
+}
+
+// the key is stored xored with the data so a slot torn by two threads writing at once fails the key check
+// instead of returning another position's data
+struct TableSlot {
+  key: AtomicU64,
+  data: AtomicU64,
+}
+
+// A fixed-size hash table of search results, indexed by the low bits of the position hash. Slots are two
+// atomics so one table can be shared between search threads without locking.
+pub struct TranspositionTable {
+  slots: Vec<TableSlot>,
+  mask: u64,
+  probes: AtomicU64,
+  hits: AtomicU64,
+  cutoffs: AtomicU64,
+  stores: AtomicU64,
+}
+
+// The number of entries of entry_size bytes a table of at most bytes bytes has: the largest power of two that
+// fits, so the index is a mask instead of a division.
+pub fn get_table_size(bytes: usize, entry_size: usize) -> u64 {
+  let wanted = (bytes / entry_size).max(1) as u64;
+  return 1u64 << (63 - wanted.leading_zeros());
+}
+
+impl TranspositionTable {
+  pub fn new(megabytes: usize) -> TranspositionTable {
+    let size = get_table_size(megabytes.max(1) * 1024 * 1024, std::mem::size_of::<TableSlot>());
+    return TranspositionTable{
+      slots: (0..size).map(|_| TableSlot{ key: AtomicU64::new(0), data: AtomicU64::new(0) }).collect(),
+      mask: size - 1,
+      probes: AtomicU64::new(0),
+      hits: AtomicU64::new(0),
+      cutoffs: AtomicU64::new(0),
+      stores: AtomicU64::new(0),
+    };
+  }
+
+  pub fn probe(&self, hash: u64) -> Option<TableEntry> {
+    self.probes.fetch_add(1, Ordering::Relaxed);
+    let slot = &self.slots[(hash & self.mask) as usize];
+    let data = slot.data.load(Ordering::Relaxed);
+    if data == 0 || slot.key.load(Ordering::Relaxed) ^ data != hash {
+      return None;
+    }
+    self.hits.fetch_add(1, Ordering::Relaxed);
+    return Some(unpack_entry(data));
+  }
+
+  // keeps whatever is already stored for the same position when it came from a deeper search
+  pub fn store(&self, hash: u64, entry: TableEntry) {
+    let slot = &self.slots[(hash & self.mask) as usize];
+    let old_data = slot.data.load(Ordering::Relaxed);
+    if old_data != 0 && slot.key.load(Ordering::Relaxed) ^ old_data == hash {
+      let old_entry = unpack_entry(old_data);
+      if old_entry.depth > entry.depth && entry.bound != Bound::Exact {
+        return;
+      }
+    }
+    let data = pack_entry(&entry);
+    slot.key.store(hash ^ data, Ordering::Relaxed);
+    slot.data.store(data, Ordering::Relaxed);
+    self.stores.fetch_add(1, Ordering::Relaxed);
+  }
+
+  // for counting probes that ended the search of a node without searching its moves
+  pub fn record_cutoff(&self) {
+    self.cutoffs.fetch_add(1, Ordering::Relaxed);
+  }
+
+  pub fn get_stats(&self) -> TableStats {
+    return TableStats{
+      probes: self.probes.load(Ordering::Relaxed),
+      hits: self.hits.load(Ordering::Relaxed),
+      cutoffs: self.cutoffs.load(Ordering::Relaxed),
+      stores: self.stores.load(Ordering::Relaxed),
+    };
+  }
+
+  // permille of slots in use, estimated from the first thousand
+  pub fn get_fill(&self) -> u32 {
+    let sample = self.slots.len().min(1000);
+    let used = self.slots[..sample].iter().filter(|slot| slot.data.load(Ordering::Relaxed) != 0).count();
+    return (used * 1000 / sample) as u32;
+  }
+
+  pub fn reset_stats(&self) {
+    self.probes.store(0, Ordering::Relaxed);
+    self.hits.store(0, Ordering::Relaxed);
+    self.cutoffs.store(0, Ordering::Relaxed);
+    self.stores.store(0, Ordering::Relaxed);
+  }
+
+  pub fn clear(&self) {
+    for slot in self.slots.iter() {
+      slot.key.store(0, Ordering::Relaxed);
+      slot.data.store(0, Ordering::Relaxed);
+    }
+    self.reset_stats();
+  }
+}
+
+// from square in the low 6 bits, to square in the next 6 and the promotion piece above that, so no move is 0
+pub fn get_move_code(m: &BitMove) -> u16 {
+  let promotion = if m.promotion == NO_PIECE {
+    0
+  } else {
+    match m.promotion % 6 {
+      QUEEN => 1,
+      ROOK => 2,
+      BISHOP => 3,
+      KNIGHT => 4,
+      _ => 0,
+    }
+  };
+  return m.from as u16 | (m.to as u16) << 6 | promotion << 12;
+}
+
+// score bits in the low 32, then the move code, the depth and the bound, which is never 0 so an empty slot is
+fn pack_entry(entry: &TableEntry) -> u64 {
+  let bound = match entry.bound {
+    Bound::Exact => 1,
+    Bound::Lower => 2,
+    Bound::Upper => 3,
+  };
+  return entry.score.to_bits() as u64 | (entry.best_move as u64) << 32 | (entry.depth.clamp(0, 255) as u64) << 48 | bound << 56;
+}
+
+fn unpack_entry(data: u64) -> TableEntry {
+  return TableEntry{
+    score: f32::from_bits(data as u32),
+    best_move: (data >> 32) as u16,
+    depth: ((data >> 48) & 0xFF) as i32,
+    bound: match data >> 56 {
+      1 => Bound::Exact,
+      2 => Bound::Lower,
+      _ => Bound::Upper,
+    },
+  };
+}
+
+#[cfg(test)]
+mod tests {
+  use std::{collections::HashSet, sync::atomic::Ordering, thread};
+  use crate::bitboard::{BitMove, KNIGHT, MoveList, NO_PIECE, PAWN, QUEEN, from_chess_board};
+  use crate::chess_board::from_forsyth_edwards_notation;
+  use crate::search::MATE_SCORE;
+  use super::{Bound, TableEntry, TranspositionTable, get_move_code, get_table_size, pack_entry, unpack_entry};
+
+  fn create_entry(depth: i32, bound: Bound, score: f32) -> TableEntry {
+    return TableEntry{ depth: depth, bound: bound, score: score, best_move: 0 };
+  }
+
+  fn create_move(from: u8, to: u8, promotion: u8) -> BitMove {
+    return BitMove{ from: from, to: to, piece: PAWN, capture: NO_PIECE, promotion: promotion, flags: 0 };
+  }
+
+  // a key that lands in the same slot as key in a table of the smallest size
+  fn get_rival_key(key: u64) -> u64 {
+    return key ^ (1 << 40);
+  }
+
+  #[test]
+  fn pack_round_trips() {
+    let promotion = create_move(52, 60, QUEEN);
+    for entry in [
+      TableEntry{ depth: 0, bound: Bound::Exact, score: 0.0, best_move: 0 },
+      TableEntry{ depth: 7, bound: Bound::Lower, score: -3.25, best_move: get_move_code(&promotion) },
+      TableEntry{ depth: 255, bound: Bound::Upper, score: MATE_SCORE - 3.0, best_move: u16::MAX },
+      TableEntry{ depth: 12, bound: Bound::Exact, score: -(MATE_SCORE - 8.0), best_move: 1 },
+    ] {
+      let unpacked = unpack_entry(pack_entry(&entry));
+      assert_eq!((unpacked.depth, unpacked.bound, unpacked.score, unpacked.best_move), (entry.depth, entry.bound, entry.score, entry.best_move));
+      // the bound is never packed as 0, which marks an empty slot
+      assert_ne!(pack_entry(&entry), 0);
+    }
+    // depths outside what fits are kept in range
+    assert_eq!(unpack_entry(pack_entry(&create_entry(300, Bound::Exact, 1.0))).depth, 255);
+    assert_eq!(unpack_entry(pack_entry(&create_entry(-2, Bound::Exact, 1.0))).depth, 0);
+  }
+
+  #[test]
+  fn move_codes() {
+    // every legal move, promotions included, has a code of its own that can't be taken for no move
+    let position = from_chess_board(&from_forsyth_edwards_notation("r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 b kq - 0 1").unwrap());
+    let mut moves = MoveList::new();
+    position.generate_legal_moves(&mut moves);
+    let codes: HashSet<u16> = moves.as_slice().iter().map(get_move_code).collect();
+    assert_eq!(codes.len(), moves.len());
+    assert!(!codes.contains(&0));
+    let queen = create_move(52, 60, QUEEN);
+    let knight = create_move(52, 60, KNIGHT);
+    let entry = TableEntry{ depth: 1, bound: Bound::Exact, score: 0.0, best_move: get_move_code(&queen) };
+    assert!(entry.is_best_move(&queen));
+    assert!(!entry.is_best_move(&knight));
+  }
+
+  #[test]
+  fn table_sizes() {
+    assert_eq!(get_table_size(1 << 20, 16), 1 << 16);
+    assert_eq!(get_table_size(1000, 16), 32);
+    assert_eq!(get_table_size(10, 16), 1);
+    assert_eq!(TranspositionTable::new(0).slots.len(), 1 << 16);
+    assert_eq!(TranspositionTable::new(3).slots.len(), 1 << 17);
+  }
+
+  #[test]
+  fn replacement() {
+    let table = TranspositionTable::new(1);
+    let key = 0x1234_5678_9abc_def0;
+    table.store(key, create_entry(6, Bound::Lower, 1.0));
+    // a shallower bound doesn't replace a deeper result for the same position
+    table.store(key, create_entry(3, Bound::Upper, 2.0));
+    assert_eq!(table.probe(key).unwrap().score, 1.0);
+    // an exact score does, as does anything at least as deep
+    table.store(key, create_entry(2, Bound::Exact, 3.0));
+    assert_eq!(table.probe(key).unwrap().score, 3.0);
+    table.store(key, create_entry(2, Bound::Lower, 4.0));
+    assert_eq!(table.probe(key).unwrap().score, 4.0);
+    // another position in the same slot always replaces it
+    table.store(get_rival_key(key), create_entry(1, Bound::Upper, 5.0));
+    assert!(table.probe(key).is_none());
+    assert_eq!(table.probe(get_rival_key(key)).unwrap().score, 5.0);
+    let stats = table.get_stats();
+    assert_eq!((stats.probes, stats.hits, stats.stores), (5, 4, 4));
+    table.clear();
+    assert!(table.probe(get_rival_key(key)).is_none());
+    assert_eq!(table.get_fill(), 0);
+  }
+
+  #[test]
+  fn torn_slots_miss() {
+    let table = TranspositionTable::new(1);
+    let key = 0x0fed_cba9_8765_4321;
+    let rival = get_rival_key(key);
+    table.store(key, create_entry(4, Bound::Exact, 1.0));
+    // another thread got as far as writing the data of its entry but not the key
+    let slot = &table.slots[(key & table.mask) as usize];
+    slot.data.store(pack_entry(&create_entry(9, Bound::Lower, 2.0)), Ordering::Relaxed);
+    assert!(table.probe(key).is_none());
+    assert!(table.probe(rival).is_none());
+  }
+
+  #[test]
+  fn threads_never_see_each_others_entries() {
+    let table = TranspositionTable::new(1);
+    // every key stores its own score, and the keys share a handful of slots so writes race on them
+    let get_key = |n: u64| -> u64 { return (n << 20) | (n % 8); };
+    thread::scope(|scope| {
+      for t in 0..4u64 {
+        let table = &table;
+        scope.spawn(move || {
+          for i in 0..50_000u64 {
+            let n = (i * 7 + t * 13) % 1000;
+            table.store(get_key(n), create_entry((n % 50) as i32, Bound::Exact, n as f32));
+            let probed = (i * 11 + t) % 1000;
+            if let Some(entry) = table.probe(get_key(probed)) {
+              assert_eq!(entry.score, probed as f32);
+              assert_eq!(entry.depth, (probed % 50) as i32);
+            }
+          }
+        });
+      }
+    });
+  }
+}
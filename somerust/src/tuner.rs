@@ -0,0 +1,372 @@
+use std::{fmt, fs, sync::Arc, thread, time::{Duration, Instant}};
+
+use crate::{bitboard::{MoveList, NO_PIECE, Position, WHITE, from_chess_board}, chess_board::{FenError, from_forsyth_edwards_notation}, chess_scoring::{Evaluator, create_evaluator, score_position}, eval_params::EvalParams, quiescence::quiescence, search::{BLACK_WIN_SCORE, SearchOptions, SearchStats, WHITE_WIN_SCORE}};
+
+// plies of captures played out to find the quiet position a labelled position is scored by
+const RESOLVE_DEPTH: i32 = 8;
+
+// A position from a finished game and how the game ended, 1 for a white win, 0.5 for a draw and 0 for a loss.
+#[derive(Debug, Clone)]
+pub struct TuningPosition {
+  pub position: Position,
+  pub result: f32,
+}
+
+#[derive(Debug, Clone)]
+pub enum TuningErrorKind {
+  Io(String),
+  InvalidFen(FenError),
+  // neither a c9 opcode nor a result after a semicolon
+  MissingResult,
+  InvalidResult(String),
+}
+
+// line is the 1-based line of the file where the problem was found, 0 when the file couldn't be read
+#[derive(Debug, Clone)]
+pub struct TuningError {
+  pub line: usize,
+  pub kind: TuningErrorKind,
+}
+
+impl fmt::Display for TuningError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    return match &self.kind {
+      TuningErrorKind::Io(message) => write!(f, "{}", message),
+      TuningErrorKind::InvalidFen(e) => write!(f, "invalid fen at line {}: {}", self.line, e),
+      TuningErrorKind::MissingResult => write!(f, "no result at line {}", self.line),
+      TuningErrorKind::InvalidResult(result) => write!(f, "invalid result {} at line {}", result, self.line),
+    };
+  }
+}
+
+fn parse_result(result: &str) -> Option<f32> {
+  return match result.trim().trim_matches('"') {
+    "1-0" | "1" | "1.0" => Some(1.0),
+    "0-1" | "0" | "0.0" => Some(0.0),
+    "1/2-1/2" | "0.5" => Some(0.5),
+    _ => None,
+  };
+}
+
+// Reads labelled positions, one to a line, either as EPD with the result in a c9 opcode, like
+// `<epd> c9 "1-0";`, or as `<fen>;<result>`. Blank lines are skipped.
+pub fn parse_tuning_positions(text: &str) -> Result<Vec<TuningPosition>, TuningError> {
+  let mut positions = Vec::new();
+  for (index, line) in text.lines().enumerate() {
+    let error = |kind| TuningError{ line: index + 1, kind: kind };
+    let line = line.trim();
+    if line.is_empty() {
+      continue;
+    }
+    let (fen, result) = match line.find(" c9 ") {
+      Some(c9) => {
+        // the four board fields of an EPD make a fen without the move counters
+        let fields: Vec<&str> = line[..c9].split_whitespace().take(4).collect();
+        let opcode = line[c9 + 4..].split(';').next().unwrap();
+        (fields.join(" "), opcode)
+      }
+      None => match line.split_once(';') {
+        Some((fen, result)) => (fen.trim().to_string(), result),
+        None => return Err(error(TuningErrorKind::MissingResult)),
+      },
+    };
+    let result = match parse_result(result) {
+      Some(r) => r,
+      None => return Err(error(TuningErrorKind::InvalidResult(result.trim().to_string()))),
+    };
+    match from_forsyth_edwards_notation(&fen) {
+      Ok(board) => positions.push(TuningPosition{ position: from_chess_board(&board), result: result }),
+      Err(e) => return Err(error(TuningErrorKind::InvalidFen(e))),
+    }
+  }
+  return Ok(positions);
+}
+
+pub fn load_tuning_positions(path: &str) -> Result<Vec<TuningPosition>, TuningError> {
+  return match fs::read_to_string(path) {
+    Ok(text) => parse_tuning_positions(&text),
+    Err(e) => Err(TuningError{ line: 0, kind: TuningErrorKind::Io(format!("couldn't read {}: {}", path, e)) }),
+  };
+}
+
+#[derive(Debug, Clone)]
+pub struct TuneOptions {
+  pub threads: usize,
+  // passes over every parameter; tuning stops early once a pass changes nothing
+  pub max_passes: usize,
+  // how far a parameter is moved at a time, in pawns
+  pub step: f32,
+}
+
+impl Default for TuneOptions {
+  fn default() -> TuneOptions {
+    return TuneOptions{
+      threads: 1,
+      max_passes: 100,
+      step: 0.01,
+    };
+  }
+}
+
+#[derive(Debug, Clone)]
+pub struct TuneResult {
+  pub params: EvalParams,
+  // positions scored, leaving out those with the side to move in check
+  pub positions: usize,
+  // the scaling of scores in pawns to expected results, fitted to the starting parameters
+  pub k: f32,
+  // mean squared error of the static scores of the quiet positions, which is what tuning minimises
+  pub error_before: f64,
+  pub error_after: f64,
+  // the same with each position scored by a quiescence search of its own, as the engine would score it
+  pub quiescence_error_before: f64,
+  pub quiescence_error_after: f64,
+  pub passes: usize,
+  pub changed: usize,
+  pub elapsed: Duration,
+}
+
+impl TuneResult {
+  pub fn to_string(&self) -> String {
+    return format!("positions {}\nk {:.4}\nerror before {:.6} after {:.6}\nquiescence error before {:.6} after {:.6}\npasses {}\nvalues changed {}\ntime {} s",
+      self.positions, self.k, self.error_before, self.error_after, self.quiescence_error_before, self.quiescence_error_after,
+      self.passes, self.changed, self.elapsed.as_secs());
+  }
+}
+
+// the expected result from white's side for a score in pawns from white's side
+fn get_expected_result(score: f32, k: f32) -> f64 {
+  return 1.0 / (1.0 + 10f64.powf(-(k * score) as f64 / 4.0));
+}
+
+// Mean squared error of the expected results, with the positions split between threads. Positions are scored
+// as they stand, or with a quiescence search of their own when quiescent is set. evaluators holds one per
+// thread, kept from call to call, as making a new one means allocating its pawn cache again.
+fn get_error(positions: &[TuningPosition], params: &EvalParams, k: f32, threads: usize, evaluators: &mut Vec<Evaluator>, quiescent: bool) -> f64 {
+  if positions.is_empty() {
+    return 0.0;
+  }
+  let params = Arc::new(params.clone());
+  let options = SearchOptions{ eval_params: params.clone(), ..Default::default() };
+  let chunk_size = positions.len().div_ceil(threads.max(1));
+  while evaluators.len() < positions.len().div_ceil(chunk_size) {
+    evaluators.push(create_evaluator(params.clone()));
+  }
+  let total: f64 = thread::scope(|scope| {
+    let workers: Vec<_> = positions.chunks(chunk_size).zip(evaluators.iter_mut()).map(|(chunk, evaluator)| {
+      evaluator.set_params(params.clone());
+      let options = &options;
+      return scope.spawn(move || {
+        let mut total = 0.0;
+        for p in chunk.iter() {
+          let score = if quiescent { get_quiescence_score(&p.position, options, evaluator) } else { evaluator.score_position(&p.position) };
+          total += (p.result as f64 - get_expected_result(score, k)).powi(2);
+        }
+        return total;
+      });
+    }).collect();
+    return workers.into_iter().map(|w| w.join().unwrap()).sum();
+  });
+  return total / positions.len() as f64;
+}
+
+fn get_quiescence_score(position: &Position, options: &SearchOptions, evaluator: &mut Evaluator) -> f32 {
+  let mut stats = SearchStats::default();
+  return quiescence(&mut position.clone(), BLACK_WIN_SCORE, WHITE_WIN_SCORE, options.quiescence_depth, 0, options, evaluator, &mut stats);
+}
+
+// Plays out captures and promotions from a position, with the side to move free to stand pat, and returns the
+// quiet position at the end of the best line along with its score from the side to move's point of view.
+// Tuning scores these instead of searching every position again for every change it tries.
+fn resolve(position: &mut Position, mut alpha: f32, beta: f32, depth: i32, params: &EvalParams) -> (f32, Position) {
+  let score = score_position(position, params);
+  let stand_pat = if position.side_to_move == WHITE { score } else { -score };
+  if depth <= 0 || stand_pat >= beta {
+    return (stand_pat, *position);
+  }
+  let mut best = (stand_pat, *position);
+  alpha = f32::max(alpha, stand_pat);
+  let mut moves = MoveList::new();
+  position.generate_legal_moves(&mut moves);
+  for m in moves.as_slice() {
+    if !m.is_capture() && m.promotion == NO_PIECE {
+      continue;
+    }
+    let undo = position.make_move(m);
+    let (score, leaf) = resolve(position, -beta, -alpha, depth - 1, params);
+    position.unmake_move(m, undo);
+    if -score > best.0 {
+      best = (-score, leaf);
+      alpha = f32::max(alpha, -score);
+      if alpha >= beta {
+        break;
+      }
+    }
+  }
+  return best;
+}
+
+// the k that makes the starting parameters predict the results best, found by narrowing in on it
+fn fit_k(positions: &[TuningPosition], params: &EvalParams, threads: usize, evaluators: &mut Vec<Evaluator>) -> f32 {
+  let mut k = 1.0;
+  let mut step = 0.5;
+  let mut error = get_error(positions, params, k, threads, evaluators, false);
+  while step > 0.001 {
+    let mut improved = false;
+    for candidate in [k + step, k - step] {
+      let candidate_error = get_error(positions, params, candidate, threads, evaluators, false);
+      if candidate > 0.0 && candidate_error < error {
+        k = candidate;
+        error = candidate_error;
+        improved = true;
+        break;
+      }
+    }
+    if !improved {
+      step /= 2.0;
+    }
+  }
+  return k;
+}
+
+fn get_values(params: &mut EvalParams) -> Vec<f32> {
+  let mut values = Vec::new();
+  for (_, weights) in params.get_fields() {
+    values.extend(weights.iter().map(|w| w.middlegame));
+    values.extend(weights.iter().map(|w| w.endgame));
+  }
+  return values;
+}
+
+fn set_values(params: &mut EvalParams, values: &[f32]) {
+  let mut values = values.iter();
+  for (_, weights) in params.get_fields() {
+    for w in weights.iter_mut() {
+      w.middlegame = *values.next().unwrap();
+    }
+    for w in weights.iter_mut() {
+      w.endgame = *values.next().unwrap();
+    }
+  }
+}
+
+// Texel tuning: every value of the parameters is moved a step up, or failing that a step down, whenever that
+// lowers the error of predicting the game results from the scores, until a pass over all of them changes
+// nothing. on_pass is called with the parameters and error after each pass, so they can be saved as it goes.
+pub fn tune(positions: &[TuningPosition], start: &EvalParams, options: &TuneOptions, on_pass: &mut dyn FnMut(usize, &EvalParams, f64)) -> TuneResult {
+  let begin = Instant::now();
+  let threads = options.threads.max(1);
+  let mut quiet = Vec::new();
+  for p in positions.iter() {
+    if p.position.is_in_check() {
+      continue;
+    }
+    let (_, leaf) = resolve(&mut p.position.clone(), BLACK_WIN_SCORE, WHITE_WIN_SCORE, RESOLVE_DEPTH, start);
+    quiet.push(TuningPosition{ position: leaf, result: p.result });
+  }
+  let scored: Vec<TuningPosition> = positions.iter().filter(|p| !p.position.is_in_check()).cloned().collect();
+  let mut evaluators = Vec::new();
+  let k = fit_k(&quiet, start, threads, &mut evaluators);
+  let error_before = get_error(&quiet, start, k, threads, &mut evaluators, false);
+  let quiescence_error_before = get_error(&scored, start, k, threads, &mut evaluators, true);
+
+  let mut params = start.clone();
+  let mut values = get_values(&mut params);
+  let start_values = values.clone();
+  let mut error = error_before;
+  let mut passes = 0;
+  while passes < options.max_passes {
+    passes += 1;
+    let mut improved = false;
+    for i in 0..values.len() {
+      let original = values[i];
+      for delta in [options.step, -options.step] {
+        values[i] = original + delta;
+        set_values(&mut params, &values);
+        let trial_error = get_error(&quiet, &params, k, threads, &mut evaluators, false);
+        if trial_error < error {
+          error = trial_error;
+          improved = true;
+          break;
+        }
+        values[i] = original;
+      }
+      set_values(&mut params, &values);
+    }
+    on_pass(passes, &params, error);
+    if !improved {
+      break;
+    }
+  }
+  return TuneResult{
+    positions: quiet.len(),
+    k: k,
+    error_before: error_before,
+    error_after: error,
+    quiescence_error_before: quiescence_error_before,
+    quiescence_error_after: get_error(&scored, &params, k, threads, &mut evaluators, true),
+    passes: passes,
+    changed: values.iter().zip(start_values.iter()).filter(|(a, b)| a != b).count(),
+    elapsed: Instant::now() - begin,
+    params: params,
+  };
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::eval_params::EvalParams;
+  use super::{TuneOptions, TuningErrorKind, get_error, parse_tuning_positions, tune};
+
+  const START: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq -";
+
+  #[test]
+  fn parses_both_formats() {
+    let text = format!("{} c9 \"1-0\";\n\n{} 0 1; 0-1\n{} 0 1;1/2-1/2\n{} c9 \"0.5\"; id \"draw\";\n", START, START, START, START);
+    let positions = parse_tuning_positions(&text).unwrap();
+    let results: Vec<f32> = positions.iter().map(|p| p.result).collect();
+    assert_eq!(results, vec![1.0, 0.0, 0.5, 0.5]);
+  }
+
+  #[test]
+  fn errors_name_the_line() {
+    let error = parse_tuning_positions(&format!("{} 0 1;1-0\n{} 0 1\n", START, START)).unwrap_err();
+    assert!(matches!(error.kind, TuningErrorKind::MissingResult));
+    assert_eq!(error.line, 2);
+    assert_eq!(error.to_string(), "no result at line 2");
+    let error = parse_tuning_positions(&format!("\n{} c9 \"2-0\";", START)).unwrap_err();
+    assert!(matches!(error.kind, TuningErrorKind::InvalidResult(ref r) if r == "\"2-0\""));
+    assert_eq!(error.line, 2);
+    let error = parse_tuning_positions("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP w KQkq - 0 1;1-0").unwrap_err();
+    assert!(matches!(error.kind, TuningErrorKind::InvalidFen(_)));
+    assert_eq!(error.line, 1);
+  }
+
+  #[test]
+  fn error_with_no_threads() {
+    let positions = parse_tuning_positions(&format!("{} 0 1;1/2-1/2", START)).unwrap();
+    let mut evaluators = Vec::new();
+    // the starting position scores level, right on the draw it is labelled with
+    assert!(get_error(&positions, &EvalParams::default(), 1.0, 0, &mut evaluators, false) < 1e-6);
+    assert_eq!(evaluators.len(), 1);
+  }
+
+  #[test]
+  fn a_pass_lowers_the_error() {
+    // a queen up wins, but a knight up loses, so the knight is worth less than the default parameters say
+    let text = [
+      "4k3/8/8/8/8/8/8/3QK3 w - - 0 1;1-0",
+      "3qk3/8/8/8/8/8/8/4K3 w - - 0 1;0-1",
+      "4k3/pppp4/8/8/8/8/PPPP4/3QK3 b - - 0 1;1-0",
+      "3qk3/pppp4/8/8/8/8/PPPP4/4K3 w - - 0 1;0-1",
+      "4k3/8/8/8/8/8/8/3NK3 w - - 0 1;0-1",
+      "4k3/pppp4/8/8/8/8/PPPP4/3NK3 b - - 0 1;0-1",
+      "3nk3/8/8/8/8/8/8/4K3 w - - 0 1;1-0",
+    ].join("\n");
+    let positions = parse_tuning_positions(&text).unwrap();
+    let options = TuneOptions{ threads: 2, max_passes: 1, ..Default::default() };
+    let result = tune(&positions, &EvalParams::default(), &options, &mut |_, _, _| {});
+    assert_eq!(result.passes, 1);
+    assert!(result.changed > 0);
+    assert!(result.error_after < result.error_before, "{} -> {}", result.error_before, result.error_after);
+  }
+}
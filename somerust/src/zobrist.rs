@@ -0,0 +1,123 @@
+use std::collections::HashSet;
+
+use crate::{castling::Castling, chess_color::ChessColor, chess_square::ChessSquare};
+
+pub struct ZobristKeys {
+  // indexed by piece (see get_piece_index) and then square, y * 8 + x
+  pub pieces: [[u64; 64]; 12],
+  pub black_to_move: u64,
+  pub castling: [u64; 4],
+  pub en_passant_file: [u64; 8],
+}
+
+// splitmix64, so the keys are fixed at compile time and hashes are stable between runs
+const fn next_random(state: u64) -> (u64, u64) {
+  let state = state.wrapping_add(0x9E3779B97F4A7C15);
+  let mut z = state;
+  z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+  z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+  return (state, z ^ (z >> 31));
+}
+
+const fn generate_keys() -> ZobristKeys {
+  let mut keys = ZobristKeys{
+    pieces: [[0; 64]; 12],
+    black_to_move: 0,
+    castling: [0; 4],
+    en_passant_file: [0; 8],
+  };
+  let mut state = 0x536F6D6552757374;
+  let mut piece = 0;
+  while piece < 12 {
+    let mut square = 0;
+    while square < 64 {
+      let (next_state, key) = next_random(state);
+      state = next_state;
+      keys.pieces[piece][square] = key;
+      square += 1;
+    }
+    piece += 1;
+  }
+  let (next_state, key) = next_random(state);
+  state = next_state;
+  keys.black_to_move = key;
+  let mut i = 0;
+  while i < 4 {
+    let (next_state, key) = next_random(state);
+    state = next_state;
+    keys.castling[i] = key;
+    i += 1;
+  }
+  let mut file = 0;
+  while file < 8 {
+    let (next_state, key) = next_random(state);
+    state = next_state;
+    keys.en_passant_file[file] = key;
+    file += 1;
+  }
+  return keys;
+}
+
+pub const ZOBRIST_KEYS: ZobristKeys = generate_keys();
+
+pub fn get_piece_index(piece: &ChessSquare) -> usize {
+  return match piece {
+    ChessSquare::Empty => panic!("empty has no zobrist key"),
+
+    ChessSquare::WhitePawn => 0,
+    ChessSquare::WhiteRook => 1,
+    ChessSquare::WhiteKnight => 2,
+    ChessSquare::WhiteBishop => 3,
+    ChessSquare::WhiteQueen => 4,
+    ChessSquare::WhiteKing => 5,
+
+    ChessSquare::BlackPawn => 6,
+    ChessSquare::BlackRook => 7,
+    ChessSquare::BlackKnight => 8,
+    ChessSquare::BlackBishop => 9,
+    ChessSquare::BlackQueen => 10,
+    ChessSquare::BlackKing => 11,
+  };
+}
+
+pub fn get_piece_key(piece: &ChessSquare, x: usize, y: usize) -> u64 {
+  return ZOBRIST_KEYS.pieces[get_piece_index(piece)][y * 8 + x];
+}
+
+pub fn get_castling_key(castling: &Castling) -> u64 {
+  return match castling {
+    Castling::WhiteShort => ZOBRIST_KEYS.castling[0],
+    Castling::WhiteLong => ZOBRIST_KEYS.castling[1],
+    Castling::BlackShort => ZOBRIST_KEYS.castling[2],
+    Castling::BlackLong => ZOBRIST_KEYS.castling[3],
+  };
+}
+
+pub fn get_castling_rights_key(available_castling: &HashSet<Castling>) -> u64 {
+  let mut key = 0;
+  for c in available_castling.iter() {
+    key ^= get_castling_key(c);
+  }
+  return key;
+}
+
+// en_passant_file should only be given when the side to move has a pawn that could make the capture, so that
+// positions which only differ by an unusable en passant square hash the same
+pub fn compute_hash(squares: &[[ChessSquare; 8]; 8], current_player: &ChessColor, available_castling: &HashSet<Castling>, en_passant_file: Option<usize>) -> u64 {
+  let mut hash = 0;
+  for y in 0..8 {
+    for x in 0..8 {
+      if squares[y][x] != ChessSquare::Empty {
+        hash ^= get_piece_key(&squares[y][x], x, y);
+      }
+    }
+  }
+  if current_player == &ChessColor::Black {
+    hash ^= ZOBRIST_KEYS.black_to_move;
+  }
+  hash ^= get_castling_rights_key(available_castling);
+  if let Some(file) = en_passant_file {
+    hash ^= ZOBRIST_KEYS.en_passant_file[file];
+  }
+  return hash;
+}